@@ -0,0 +1,150 @@
+//! Emulator throughput. Run with `cargo bench -p app --bench emu`.
+
+use app::term::Emu;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+const COLS: usize = 200;
+const ROWS: usize = 50;
+
+/// `n` full-width lines of plain text, each ending in CR LF.
+fn lines(n: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(n * (COLS + 2));
+    for i in 0..n {
+        let line = format!("{i:>8} ");
+        out.extend_from_slice(line.as_bytes());
+        out.extend((0..COLS - line.len()).map(|j| b'a' + (j % 26) as u8));
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// Line feeds at the bottom of a full screen, which is what `cat`ing a big
+/// file or a chatty build log boils down to.
+fn scroll(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scroll");
+    let feeds = vec![b'\n'; 10_000];
+    group.throughput(Throughput::Elements(feeds.len() as u64));
+    group.bench_function("linefeed", |b| {
+        let mut emu = Emu::new(COLS, ROWS);
+        emu.on_bytes(&lines(ROWS));
+        b.iter(|| emu.on_bytes(black_box(&feeds)));
+    });
+    // A scroll region that excludes the last row, like a status line.
+    group.bench_function("linefeed_region", |b| {
+        let mut emu = Emu::new(COLS, ROWS);
+        emu.on_bytes(format!("\x1b[1;{}r\x1b[{};1H", ROWS - 1, ROWS - 1).as_bytes());
+        b.iter(|| emu.on_bytes(black_box(&feeds)));
+    });
+    group.finish();
+}
+
+/// Printing text into a screen with a full scrollback.
+fn output(c: &mut Criterion) {
+    let mut group = c.benchmark_group("output");
+    let text = lines(2_000);
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.bench_function("full_lines", |b| {
+        let mut emu = Emu::new(COLS, ROWS);
+        emu.on_bytes(&lines(emu.scrollback_limit + ROWS));
+        b.iter(|| emu.on_bytes(black_box(&text)));
+    });
+    group.finish();
+}
+
+/// Typical output mixes, each about 1 MB, fed in 4 KiB reads like the PTY
+/// reader thread does.
+fn print(c: &mut Criterion) {
+    let mut group = c.benchmark_group("print");
+    let cases: [(&str, Vec<u8>); 3] = [
+        ("plain", plain()),
+        ("colored", colored()),
+        ("cursor", cursor_moves()),
+    ];
+    for (name, data) in cases {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(name, |b| {
+            let mut emu = Emu::new(COLS, ROWS);
+            b.iter(|| {
+                for chunk in data.chunks(4096) {
+                    emu.on_bytes(black_box(chunk));
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Log-like lines of varying length.
+fn plain() -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0usize;
+    while out.len() < 1 << 20 {
+        let len = 20 + (i * 37) % 150;
+        out.extend(format!("{i:06} ").bytes());
+        out.extend((0..len).map(|j| b"the quick brown fox jumps over "[j % 31]));
+        out.extend_from_slice(b"\r\n");
+        i += 1;
+    }
+    out
+}
+
+/// `ls --color` / compiler output: a color change every word or two.
+fn colored() -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0usize;
+    while out.len() < 1 << 20 {
+        for w in 0..12 {
+            let sgr = match (i + w) % 4 {
+                0 => "\x1b[0m".to_string(),
+                1 => format!("\x1b[1;3{}m", (i + w) % 8),
+                2 => format!("\x1b[38;5;{}m", (i * 7 + w) % 256),
+                _ => format!("\x1b[38;2;{};{};{}m", i % 256, w * 20, 128),
+            };
+            out.extend(sgr.bytes());
+            out.extend(format!("word{w} ").bytes());
+        }
+        out.extend_from_slice(b"\x1b[m\r\n");
+        i += 1;
+    }
+    out
+}
+
+/// Full-screen TUI redraws (htop, vim): absolute moves, short updates and
+/// line erases all over the screen, no scrolling.
+fn cursor_moves() -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0usize;
+    while out.len() < 1 << 20 {
+        let y = 1 + (i * 7) % ROWS;
+        let x = 1 + (i * 13) % (COLS - 20);
+        out.extend(format!("\x1b[{y};{x}H\x1b[K\x1b[3{}m{:>8}\x1b[m", i % 8, i).bytes());
+        if i % 50 == 0 {
+            out.extend(b"\x1b[H\x1b[2J");
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Filling an empty emulator's scrollback to its limit, which is dominated
+/// by allocating and writing row storage.
+fn fill_scrollback(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scrollback");
+    let text = lines(10_000 + ROWS);
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.sample_size(20);
+    group.bench_function("fill_10k", |b| {
+        b.iter_batched(
+            || Emu::new(COLS, ROWS),
+            |mut emu| {
+                emu.on_bytes(&text);
+                emu
+            },
+            BatchSize::LargeInput,
+        );
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scroll, output, print, fill_scrollback);
+criterion_main!(benches);
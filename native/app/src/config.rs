@@ -0,0 +1,209 @@
+use anyhow::Result;
+use dirs::config_dir;
+use ptycore::ShellPrefs;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::term::emu::{CursorShape, CursorStyle, Limits, WidthPolicy};
+
+/// User settings read from `<config_dir>/terminal-ui/config.toml`.
+/// Every section is optional; missing keys fall back to the defaults below.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub shell: Shell,
+    pub cursor: Cursor,
+    pub bell: Bell,
+    pub scrollback: Scrollback,
+    pub policy: Policy,
+    /// Caps on what terminal output may cost.
+    pub limits: Limits,
+    pub session: Session,
+    /// Character widths; must match what programs on the host assume.
+    pub unicode: WidthPolicy,
+    pub bidi: Bidi,
+}
+
+/// What runs in the terminal: the login shell unless `program` is set.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Shell {
+    /// Program to run instead of the shell, e.g. `"ssh"` or `"python3"`.
+    pub program: Option<String>,
+    pub args: Vec<String>,
+    /// Variables to set for it.
+    pub env: BTreeMap<String, String>,
+    /// Inherited variables to remove.
+    pub env_remove: Vec<String>,
+    /// Starting directory; the home directory if unset.
+    pub cwd: Option<PathBuf>,
+    /// Start bash, zsh or fish as a login shell. Like `interactive`, not
+    /// applied to a `program` that has `args`.
+    pub login: bool,
+    /// Start bash or zsh as an interactive shell.
+    pub interactive: bool,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self {
+            program: None,
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            env_remove: Vec::new(),
+            cwd: None,
+            login: true,
+            interactive: true,
+        }
+    }
+}
+
+impl Shell {
+    pub fn to_prefs(&self) -> ShellPrefs {
+        ShellPrefs {
+            login: self.login,
+            interactive: self.interactive,
+            program: self.program.clone(),
+            args: self.args.clone(),
+            env: self.env.clone().into_iter().collect(),
+            env_remove: self.env_remove.clone(),
+            cwd: self.cwd.clone(),
+            ..ShellPrefs::default()
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Cursor {
+    pub style: CursorShape,
+    pub blink: bool,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            style: CursorShape::Block,
+            blink: true,
+        }
+    }
+}
+
+impl Cursor {
+    pub fn to_style(&self) -> CursorStyle {
+        CursorStyle {
+            shape: self.style,
+            blinking: self.blink,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Bell {
+    /// Flash the terminal area.
+    pub visual: bool,
+    /// Play the alarm sound (can also be muted at runtime).
+    pub sound: bool,
+    /// Set the window urgency hint when the bell rings while unfocused.
+    pub urgent: bool,
+    /// Bells closer together than this are dropped.
+    pub min_interval_ms: u64,
+}
+
+impl Default for Bell {
+    fn default() -> Self {
+        Self {
+            visual: true,
+            sound: true,
+            urgent: true,
+            min_interval_ms: 200,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Scrollback {
+    /// Rows kept above the screen; 0 disables scrollback.
+    pub lines: usize,
+    /// Keep only the newest few thousand rows in memory and page older ones
+    /// out to the cache directory, for very large `lines`.
+    pub spill_to_disk: bool,
+    /// Show when each line arrived in a gutter left of the terminal, and
+    /// include the times in exports.
+    pub timestamps: bool,
+}
+
+impl Default for Scrollback {
+    fn default() -> Self {
+        Self {
+            lines: 10_000,
+            spill_to_disk: false,
+            timestamps: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Bidi {
+    /// Draw right-to-left scripts (Arabic, Hebrew) in reading order, with
+    /// Arabic letters joined. Off, rows are drawn exactly as stored.
+    pub enabled: bool,
+}
+
+impl Default for Bidi {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// What terminal applications are allowed to do to the host window.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Policy {
+    /// Honour XTWINOPS move/resize/iconify/raise requests. Size reports are
+    /// answered regardless.
+    pub window_ops: bool,
+    /// Answer title reports (CSI 20 t / CSI 21 t). The reply is typed into
+    /// the shell, so output that sets a title could run it as a command.
+    pub title_reports: bool,
+    /// Let applications read the clipboard through OSC 52.
+    pub clipboard_read: bool,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Session {
+    /// Save the screen and scrollback on exit and bring them back on the
+    /// next start.
+    pub restore: bool,
+    /// Upper bound for the compressed scrollback in a saved session.
+    pub scrollback_kib: usize,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            restore: false,
+            scrollback_kib: 1024,
+        }
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("terminal-ui/config.toml"))
+}
+
+pub fn load_config() -> Result<Config> {
+    if let Some(path) = config_path() {
+        if let Ok(data) = fs::read_to_string(&path) {
+            let cfg: Config = toml::from_str(&data)?;
+            return Ok(cfg);
+        }
+    }
+    Ok(Config::default())
+}
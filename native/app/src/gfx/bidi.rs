@@ -0,0 +1,396 @@
+//! Bidirectional text for display.
+//!
+//! The emulator keeps rows in logical order, as programs wrote them; only
+//! drawing is reordered. [`layout`] runs a reduced Unicode Bidirectional
+//! Algorithm over a row: the paragraph is left-to-right, as in every
+//! terminal, and explicit embeddings are treated as neutrals. It returns
+//! where each cell is drawn and which glyph to use, with brackets mirrored
+//! in right-to-left runs and Arabic letters in their joined forms. The
+//! cursor and anything else mapping between screen and cells goes through
+//! the same [`Layout`], so they agree with what is drawn.
+
+use std::ops::Range;
+
+use swash::text::{BidiClass, Codepoint, JoiningType};
+
+use crate::term::emu::{Cell, Emu, VS16};
+
+/// Draw order of one row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// Visual column of every logical column.
+    visual: Vec<usize>,
+    /// Logical column drawn at every visual column.
+    logical: Vec<usize>,
+    /// Glyph for every logical column.
+    glyphs: Vec<char>,
+}
+
+impl Layout {
+    /// Where logical column `x` is drawn; past the row stays put.
+    pub fn visual_col(&self, x: usize) -> usize {
+        self.visual.get(x).copied().unwrap_or(x)
+    }
+
+    /// Which logical column is drawn at visual column `x`, e.g. under the
+    /// mouse.
+    pub fn logical_col(&self, x: usize) -> usize {
+        self.logical.get(x).copied().unwrap_or(x)
+    }
+
+    /// The logical columns under visual columns `visual`, in logical order:
+    /// what a selection dragged across the screen contains. A contiguous
+    /// stretch on screen can be scattered in storage.
+    pub fn selected_cols(&self, visual: Range<usize>) -> Vec<usize> {
+        let mut cols: Vec<usize> = visual.map(|x| self.logical_col(x)).collect();
+        cols.sort_unstable();
+        cols
+    }
+
+    /// The character to draw for logical column `x`.
+    pub fn glyph(&self, x: usize) -> char {
+        self.glyphs[x]
+    }
+}
+
+/// Bidi classes, named as in UAX #9, narrowed to what the reduced
+/// algorithm distinguishes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+enum Class {
+    L,
+    R,
+    /// Arabic letter; becomes `R` once numbers after it are resolved.
+    AL,
+    EN,
+    AN,
+    ES,
+    ET,
+    CS,
+    NSM,
+    /// Whitespace, other neutrals, and the explicit formatting characters
+    /// this reduced algorithm does not honour.
+    N,
+}
+
+impl Class {
+    fn of(c: char) -> Self {
+        match c.bidi_class() {
+            BidiClass::L => Class::L,
+            BidiClass::R => Class::R,
+            BidiClass::AL => Class::AL,
+            BidiClass::EN => Class::EN,
+            BidiClass::AN => Class::AN,
+            BidiClass::ES => Class::ES,
+            BidiClass::ET => Class::ET,
+            BidiClass::CS => Class::CS,
+            BidiClass::NSM => Class::NSM,
+            _ => Class::N,
+        }
+    }
+
+    fn is_rtl(c: char) -> bool {
+        matches!(c.bidi_class(), BidiClass::R | BidiClass::AL | BidiClass::AN)
+    }
+}
+
+/// A character with the cells it covers: a wide character's spacer, or the
+/// VS16 that widened it.
+struct Unit {
+    start: usize,
+    len: usize,
+    ch: char,
+}
+
+/// The draw order of `row`, or `None` when it has no right-to-left text
+/// and is drawn as stored.
+pub fn layout(emu: &Emu, row: &[Cell]) -> Option<Layout> {
+    if !row.iter().any(|c| Class::is_rtl(c.ch)) {
+        return None;
+    }
+    let units = units(emu, row);
+    let mut classes: Vec<Class> = units.iter().map(|u| Class::of(u.ch)).collect();
+    resolve_weak(&mut classes);
+    resolve_neutral(&mut classes);
+    let levels: Vec<u8> = classes
+        .iter()
+        .map(|c| match c {
+            Class::L => 0,
+            Class::R => 1,
+            _ => 2,
+        })
+        .collect();
+    let order = reorder(&levels);
+
+    let mut glyphs: Vec<char> = row.iter().map(|c| c.ch).collect();
+    shape_arabic(&units, &mut glyphs);
+    for (unit, &level) in units.iter().zip(&levels) {
+        if level % 2 == 1 {
+            if let Some(m) = unit.ch.mirror() {
+                glyphs[unit.start] = m;
+            }
+        }
+    }
+    let mut visual = vec![0; row.len()];
+    let mut logical = Vec::with_capacity(row.len());
+    for &i in &order {
+        let unit = &units[i];
+        let cells = visual.iter_mut().enumerate().skip(unit.start);
+        for (x, v) in cells.take(unit.len) {
+            *v = logical.len();
+            logical.push(x);
+        }
+    }
+    Some(Layout {
+        visual,
+        logical,
+        glyphs,
+    })
+}
+
+fn units(emu: &Emu, row: &[Cell]) -> Vec<Unit> {
+    let mut units = Vec::with_capacity(row.len());
+    let mut x = 0;
+    while x < row.len() {
+        let ch = row[x].ch;
+        let mut len = emu.char_width(ch);
+        if len == 1 && row.get(x + 1).is_some_and(|c| c.ch == VS16) {
+            len = 2;
+        }
+        let len = len.min(row.len() - x);
+        units.push(Unit { start: x, len, ch });
+        x += len;
+    }
+    units
+}
+
+/// Rules W1–W7.
+fn resolve_weak(classes: &mut [Class]) {
+    let n = classes.len();
+    // W1: marks take the class of what they are attached to.
+    for i in 0..n {
+        if classes[i] == Class::NSM {
+            classes[i] = if i == 0 { Class::L } else { classes[i - 1] };
+        }
+    }
+    // W2: European digits in Arabic text are Arabic numbers. The
+    // left-to-right paragraph counts as the strong type before the row.
+    let mut strong = Class::L;
+    for c in classes.iter_mut() {
+        match *c {
+            Class::L | Class::R | Class::AL => strong = *c,
+            Class::EN if strong == Class::AL => *c = Class::AN,
+            _ => {}
+        }
+    }
+    // W3
+    for c in classes.iter_mut() {
+        if *c == Class::AL {
+            *c = Class::R;
+        }
+    }
+    // W4: a single separator between two numbers of the same kind.
+    for i in 1..n.saturating_sub(1) {
+        let (prev, next) = (classes[i - 1], classes[i + 1]);
+        classes[i] = match (classes[i], prev, next) {
+            (Class::ES, Class::EN, Class::EN) => Class::EN,
+            (Class::CS, Class::EN, Class::EN) => Class::EN,
+            (Class::CS, Class::AN, Class::AN) => Class::AN,
+            (c, _, _) => c,
+        };
+    }
+    // W5: terminators next to European numbers join them.
+    let mut i = 0;
+    while i < n {
+        if classes[i] != Class::ET {
+            i += 1;
+            continue;
+        }
+        let end = (i..n).find(|&j| classes[j] != Class::ET).unwrap_or(n);
+        let touches_en =
+            (i > 0 && classes[i - 1] == Class::EN) || classes.get(end) == Some(&Class::EN);
+        if touches_en {
+            classes[i..end].fill(Class::EN);
+        }
+        i = end;
+    }
+    // W6
+    for c in classes.iter_mut() {
+        if matches!(c, Class::ES | Class::ET | Class::CS) {
+            *c = Class::N;
+        }
+    }
+    // W7: European numbers in left-to-right text are left-to-right.
+    let mut strong = Class::L;
+    for c in classes.iter_mut() {
+        match *c {
+            Class::L | Class::R => strong = *c,
+            Class::EN if strong == Class::L => *c = Class::L,
+            _ => {}
+        }
+    }
+}
+
+/// Rules N1 and N2: neutrals between text of one direction take it, others
+/// take the paragraph's, left-to-right.
+fn resolve_neutral(classes: &mut [Class]) {
+    let dir = |c: Class| match c {
+        Class::L => Class::L,
+        _ => Class::R,
+    };
+    let n = classes.len();
+    let mut i = 0;
+    while i < n {
+        if classes[i] != Class::N {
+            i += 1;
+            continue;
+        }
+        let end = (i..n).find(|&j| classes[j] != Class::N).unwrap_or(n);
+        let before = if i == 0 {
+            Class::L
+        } else {
+            dir(classes[i - 1])
+        };
+        let after = classes.get(end).map_or(Class::L, |&c| dir(c));
+        let resolved = if before == after { before } else { Class::L };
+        classes[i..end].fill(resolved);
+        i = end;
+    }
+}
+
+/// Rule L2: the visual order of units with the given embedding levels.
+fn reorder(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let max = levels.iter().copied().max().unwrap_or(0);
+    for level in (1..=max).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let end = (i..order.len())
+                .find(|&j| levels[order[j]] < level)
+                .unwrap_or(order.len());
+            order[i..end].reverse();
+            i = end;
+        }
+    }
+    order
+}
+
+/// Replace Arabic letters with the presentation form their neighbours call
+/// for (isolated, final, initial or medial), using the joining types from
+/// swash's Unicode tables. Fonts without contextual shaping in our glyph
+/// path still get connected script this way. Lam followed by alef becomes
+/// the lam-alef ligature in the lam's cell and the alef's cell draws blank.
+fn shape_arabic(units: &[Unit], glyphs: &mut [char]) {
+    let joining: Vec<JoiningType> = units.iter().map(|u| u.ch.joining_type()).collect();
+    // Transparent marks (harakat) do not break a join.
+    let neighbour = |i: usize, step: isize| -> JoiningType {
+        let mut j = i as isize + step;
+        while j >= 0 && (j as usize) < units.len() {
+            match joining[j as usize] {
+                JoiningType::T => j += step,
+                t => return t,
+            }
+        }
+        JoiningType::U
+    };
+    let mut ligated = None;
+    for (i, unit) in units.iter().enumerate() {
+        if ligated == Some(i) {
+            continue;
+        }
+        if unit.ch == '\u{644}' {
+            let next = (i + 1..units.len()).find(|&j| joining[j] != JoiningType::T);
+            if let Some((j, isolated)) = next.and_then(|j| Some((j, lam_alef(units[j].ch)?))) {
+                let joins_prev = matches!(neighbour(i, -1), JoiningType::D | JoiningType::L);
+                if let Some(f) = char::from_u32(isolated + joins_prev as u32) {
+                    glyphs[unit.start] = f;
+                    glyphs[units[j].start] = ' ';
+                    ligated = Some(j);
+                    continue;
+                }
+            }
+        }
+        let Some((first, count)) = presentation_forms(unit.ch) else {
+            continue;
+        };
+        let t = joining[i];
+        let joins_prev = matches!(t, JoiningType::D | JoiningType::R)
+            && matches!(neighbour(i, -1), JoiningType::D | JoiningType::L);
+        let joins_next = t == JoiningType::D
+            && count == 4
+            && matches!(neighbour(i, 1), JoiningType::D | JoiningType::R);
+        let form = match (joins_prev, joins_next) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (false, true) => 2,
+            (true, true) => 3,
+        };
+        if let Some(f) = char::from_u32(first + form.min(count - 1)) {
+            glyphs[unit.start] = f;
+        }
+    }
+}
+
+/// Isolated form of the ligature of lam with `alef`, if it is one of the
+/// alefs that take it; the final form follows it.
+fn lam_alef(alef: char) -> Option<u32> {
+    match alef {
+        '\u{622}' => Some(0xfef5),
+        '\u{623}' => Some(0xfef7),
+        '\u{625}' => Some(0xfef9),
+        '\u{627}' => Some(0xfefb),
+        _ => None,
+    }
+}
+
+/// The first of `c`'s forms in Arabic Presentation Forms-B and how many
+/// there are: isolated, final, initial and medial, in that order. Letters
+/// that only join on their right have the first two.
+fn presentation_forms(c: char) -> Option<(u32, u32)> {
+    // (letter, first form, number of forms)
+    const FORMS: &[(u32, u32, u32)] = &[
+        (0x0621, 0xfe80, 1),
+        (0x0622, 0xfe81, 2),
+        (0x0623, 0xfe83, 2),
+        (0x0624, 0xfe85, 2),
+        (0x0625, 0xfe87, 2),
+        (0x0626, 0xfe89, 4),
+        (0x0627, 0xfe8d, 2),
+        (0x0628, 0xfe8f, 4),
+        (0x0629, 0xfe93, 2),
+        (0x062a, 0xfe95, 4),
+        (0x062b, 0xfe99, 4),
+        (0x062c, 0xfe9d, 4),
+        (0x062d, 0xfea1, 4),
+        (0x062e, 0xfea5, 4),
+        (0x062f, 0xfea9, 2),
+        (0x0630, 0xfeab, 2),
+        (0x0631, 0xfead, 2),
+        (0x0632, 0xfeaf, 2),
+        (0x0633, 0xfeb1, 4),
+        (0x0634, 0xfeb5, 4),
+        (0x0635, 0xfeb9, 4),
+        (0x0636, 0xfebd, 4),
+        (0x0637, 0xfec1, 4),
+        (0x0638, 0xfec5, 4),
+        (0x0639, 0xfec9, 4),
+        (0x063a, 0xfecd, 4),
+        (0x0641, 0xfed1, 4),
+        (0x0642, 0xfed5, 4),
+        (0x0643, 0xfed9, 4),
+        (0x0644, 0xfedd, 4),
+        (0x0645, 0xfee1, 4),
+        (0x0646, 0xfee5, 4),
+        (0x0647, 0xfee9, 4),
+        (0x0648, 0xfeed, 2),
+        (0x0649, 0xfeef, 2),
+        (0x064a, 0xfef1, 4),
+    ];
+    let i = FORMS.binary_search_by_key(&(c as u32), |f| f.0).ok()?;
+    let (_, first, count) = FORMS[i];
+    Some((first, count))
+}
@@ -1,6 +1,7 @@
 #[allow(dead_code)]
 pub const GRID_SHADER: &str = include_str!("shaders/grid.wgsl");
 
+pub mod bidi;
 pub mod renderer;
 pub mod text;
 
@@ -1,18 +1,28 @@
 use anyhow::Result;
 use wgpu::CommandEncoder;
 
-use crate::theme::Theme;
+use crate::term::emu::{CursorShape, CursorStyle, Emu, LineAttr, Underline, VS16};
+use crate::term::export::format_time;
+
+use super::bidi;
+use crate::theme::{Palette, Theme};
 use crate::ui::theme_switcher::{OverlayBox, RowRenderItem};
 
 // If panels are placed elsewhere, adjust the path accordingly.
 #[allow(unused_imports)]
 use crate::ui::panels::Panels;
 
-pub struct Renderer;
+/// Width of the timestamp gutter in cells: `HH:MM:SS` and a space.
+pub const TIME_GUTTER_COLS: usize = 9;
+
+pub struct Renderer {
+    /// Reorder right-to-left text for display (see [`bidi`]).
+    pub bidi: bool,
+}
 
 impl Renderer {
     pub fn new() -> Self {
-        Renderer
+        Renderer { bidi: true }
     }
 
     pub fn resize(&mut self, _width: u32, _height: u32) {}
@@ -65,6 +75,219 @@ impl Renderer {
         // TODO: route to glyph renderer
     }
 
+    /// Restrict subsequent drawing to a rectangle; `None` lifts the limit.
+    pub fn set_clip(&mut self, _rect: Option<(f32, f32, f32, f32)>) {
+        // TODO: apply as scissor rect on the glyph pass
+    }
+
+    /// Draw the emulator viewport from `x0` with colors resolved through
+    /// `palette`. Wide characters cover the cells the emulator gave them,
+    /// measured with its width policy, and rows with right-to-left text are
+    /// drawn in bidi order unless disabled. Double-width rows are stretched
+    /// horizontally; double-height rows are drawn at twice the size and
+    /// clipped to their own row so the top and bottom halves together form
+    /// one tall line.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_grid(
+        &mut self,
+        enc: &mut CommandEncoder,
+        emu: &Emu,
+        palette: &Palette,
+        x0: f32,
+        cell_w: f32,
+        cell_h: f32,
+    ) {
+        let mut text = String::new();
+        for y in 0..emu.rows {
+            let row_y = y as f32 * cell_h;
+            let attr = emu.visible_line_attr(y);
+            let (scale_x, scale_y, shift_y) = match attr {
+                LineAttr::Normal => (1.0, 1.0, 0.0),
+                LineAttr::DoubleWidth => (2.0, 1.0, 0.0),
+                LineAttr::DoubleHeightTop => (2.0, 2.0, 0.0),
+                LineAttr::DoubleHeightBottom => (2.0, 2.0, -cell_h),
+            };
+            let row = emu.visible_row(y);
+            let used = if attr.is_double() {
+                emu.cols / 2
+            } else {
+                emu.cols
+            };
+            self.set_clip(Some((x0, row_y, emu.cols as f32 * cell_w, cell_h)));
+            let used = used.clamp(1, row.len());
+            let layout = if self.bidi {
+                bidi::layout(emu, &row[..used])
+            } else {
+                None
+            };
+            let mut x = 0;
+            while x < used {
+                let cell = row[x];
+                text.clear();
+                text.push(layout.as_ref().map_or(cell.ch, |l| l.glyph(x)));
+                let mut cells = emu.char_width(cell.ch);
+                if cells == 1 && row.get(x + 1).is_some_and(|c| c.ch == VS16) {
+                    // Emoji presentation across the selector's cell.
+                    text.push(VS16);
+                    cells = 2;
+                }
+                let vx = layout.as_ref().map_or(x, |l| l.visual_col(x));
+                let cx = x0 + vx as f32 * cell_w * scale_x;
+                let w = cells.min(used - x) as f32 * cell_w * scale_x;
+                x += cells;
+                let style = emu.style(cell.style);
+                let (fg, bg) = palette.cell_colors(style);
+                if bg != palette.background {
+                    let bg = bg.to_css();
+                    self.draw_rounded_rect(enc, cx, row_y, w, cell_h, &bg, "", 0.0);
+                }
+                if cell.ch != ' ' {
+                    self.draw_text(
+                        enc,
+                        cx,
+                        row_y + shift_y,
+                        &text,
+                        &fg.to_css(),
+                        cell_h * scale_y,
+                    );
+                }
+                if style.underline != Underline::None {
+                    let color = palette.underline_color(style, fg).to_css();
+                    let base = row_y + shift_y + cell_h * scale_y;
+                    self.draw_underline(
+                        enc,
+                        cx,
+                        base,
+                        w,
+                        cell_h * scale_y,
+                        style.underline,
+                        &color,
+                    );
+                }
+            }
+        }
+        self.set_clip(None);
+    }
+
+    /// Draw the UTC time each viewport row arrived (`HH:MM:SS`) in a gutter
+    /// of [`TIME_GUTTER_COLS`] cells at the left edge. A time is shown only
+    /// where it differs from the row above, so bursts of output stay readable.
+    pub fn draw_time_gutter(
+        &mut self,
+        enc: &mut CommandEncoder,
+        emu: &Emu,
+        cell_w: f32,
+        cell_h: f32,
+        color: &str,
+    ) {
+        let mut prev = String::new();
+        for y in 0..emu.rows {
+            let time = emu.visible_line_time(y);
+            if time == 0 {
+                prev.clear();
+                continue;
+            }
+            let hms = format_time(time)[11..19].to_string();
+            if hms != prev {
+                let w = TIME_GUTTER_COLS as f32 * cell_w;
+                let row_y = y as f32 * cell_h;
+                self.set_clip(Some((0.0, row_y, w, cell_h)));
+                self.draw_text(enc, 0.0, row_y, &hms, color, cell_h);
+            }
+            prev = hms;
+        }
+        self.set_clip(None);
+    }
+
+    /// Draw the debug inspector: a panel at `(x, y)` holding `lines` (see
+    /// [`crate::term::inspect`]), one per `line_h`. Lines that do not fit
+    /// in `h` are dropped from the top, so the newest log entries stay.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_inspector(
+        &mut self,
+        enc: &mut CommandEncoder,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        line_h: f32,
+        lines: &[String],
+        theme: &Theme,
+    ) {
+        self.draw_rounded_rect(
+            enc,
+            x,
+            y,
+            w,
+            h,
+            &theme.ui.panel_bg,
+            &theme.ui.panel_border,
+            1.0,
+        );
+        let pad = line_h / 2.0;
+        let fit = ((h - 2.0 * pad) / line_h).max(0.0) as usize;
+        let skip = lines.len().saturating_sub(fit);
+        self.set_clip(Some((x, y, w, h)));
+        for (i, line) in lines[skip..].iter().enumerate() {
+            let color = if line.starts_with('!') {
+                &theme.ui.accent
+            } else {
+                &theme.ui.text
+            };
+            let ly = y + pad + i as f32 * line_h;
+            self.draw_text(enc, x + pad, ly, line, color, line_h);
+        }
+        self.set_clip(None);
+    }
+
+    /// Draw one cell's underline ending at baseline `base`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_underline(
+        &mut self,
+        enc: &mut CommandEncoder,
+        x: f32,
+        base: f32,
+        w: f32,
+        cell_h: f32,
+        style: Underline,
+        color: &str,
+    ) {
+        let t = (cell_h / 16.0).max(1.0);
+        let y = base - 2.0 * t;
+        match style {
+            Underline::None => {}
+            Underline::Single => self.draw_rounded_rect(enc, x, y, w, t, color, "", 0.0),
+            Underline::Double => {
+                self.draw_rounded_rect(enc, x, y, w, t, color, "", 0.0);
+                self.draw_rounded_rect(enc, x, y - 2.0 * t, w, t, color, "", 0.0);
+            }
+            Underline::Dotted | Underline::Dashed => {
+                let (on, off) = if style == Underline::Dotted {
+                    (t, t)
+                } else {
+                    (3.0 * t, 2.0 * t)
+                };
+                let mut sx = x;
+                while sx < x + w {
+                    let seg = on.min(x + w - sx);
+                    self.draw_rounded_rect(enc, sx, y, seg, t, color, "", 0.0);
+                    sx += on + off;
+                }
+            }
+            Underline::Curly => {
+                // One sine period per cell, sampled as short steps.
+                let steps = (w / t).ceil().max(4.0) as usize;
+                let step_w = w / steps as f32;
+                for i in 0..steps {
+                    let phase = (i as f32 + 0.5) / steps as f32 * std::f32::consts::TAU;
+                    let dy = phase.sin() * t;
+                    let sx = x + i as f32 * step_w;
+                    self.draw_rounded_rect(enc, sx, y - t + dy, step_w, t, color, "", 0.0);
+                }
+            }
+        }
+    }
+
     pub fn draw_side_panel(
         &mut self,
         enc: &mut CommandEncoder,
@@ -85,6 +308,33 @@ impl Renderer {
         );
     }
 
+    /// Draw the terminal cursor in one cell. An unfocused window always gets a
+    /// hollow block, regardless of the shape the application asked for.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_cursor(
+        &mut self,
+        enc: &mut CommandEncoder,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        style: CursorStyle,
+        color: &str,
+        focused: bool,
+    ) {
+        if !focused {
+            self.draw_rounded_rect(enc, x, y, w, h, "", color, 1.0);
+            return;
+        }
+        let thickness = (h / 8.0).max(2.0);
+        let (cx, cy, cw, ch) = match style.shape {
+            CursorShape::Block => (x, y, w, h),
+            CursorShape::Underline => (x, y + h - thickness, w, thickness),
+            CursorShape::Bar => (x, y, thickness, h),
+        };
+        self.draw_rounded_rect(enc, cx, cy, cw, ch, color, "", 0.0);
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn draw_bar(
         &mut self,
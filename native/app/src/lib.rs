@@ -1,5 +1,6 @@
 //! Library façade so tests and tools can import modules without bin-only quirks.
 #![allow(clippy::needless_return, clippy::new_without_default)]
+pub mod config;
 pub mod gfx;
 pub mod term;
 pub mod theme;
@@ -1,42 +1,93 @@
 use anyhow::{Context, Result};
-use crossbeam_channel::{unbounded, Receiver};
+use crossbeam_channel::{bounded, unbounded, Receiver};
 use ptycore::{spawn_shell, ShellPrefs};
 use std::io::Read;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use wgpu::SurfaceError;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
+use winit::window::{Fullscreen, UserAttentionType, WindowBuilder};
 
-mod gfx;
-mod term;
-mod theme;
-mod ui;
+use app::{config, gfx, term, theme, ui};
+use gfx::bidi;
+use gfx::renderer::TIME_GUTTER_COLS;
 use gfx::Renderer;
-use term::Emu;
+use term::cast::{Cast, Player, Recorder};
+use term::emu::Snapshot;
+use term::event::{ProgressState, WindowOp};
+use term::export::{self, Format, Region};
+use term::inspect::inspect;
+use term::shared::SharedEmu;
+use term::{Emu, EmuEvent};
+use ui::bell::{self, Bell};
 use ui::panels::Panels;
 use ui::theme_switcher::{Action as TSAction, Key as TKey, Page as TPage, ThemeSwitcher};
 
+const CURSOR_BLINK_INTERVAL: Duration = Duration::from_millis(530);
+/// Longest we hold a frame for an application inside a mode 2026 update.
+const SYNC_UPDATE_TIMEOUT: Duration = Duration::from_millis(150);
+/// PTY reads queued for the parser thread. When it falls behind the reader
+/// blocks, and so eventually does the flooding program.
+const OUTPUT_QUEUE: usize = 256;
+/// Sequences kept in the inspector's log.
+const INSPECTOR_LOG: usize = 64;
+
+/// What the window is attached to.
+enum Launch {
+    /// A live shell, optionally recorded from the start.
+    Shell {
+        /// Program and arguments to run instead of the configured one.
+        command: Vec<String>,
+        /// Starting directory, overriding the config and a restored session.
+        cwd: Option<PathBuf>,
+        record: Option<PathBuf>,
+        record_input: bool,
+    },
+    /// Replay of an asciicast file; no shell is spawned.
+    Play { path: PathBuf, speed: f64 },
+}
+
 struct State {
     surface: wgpu::Surface,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
-    _pty: Arc<Mutex<ptycore::PtyHandle>>,
-    rx: Receiver<Vec<u8>>,
-    emu: Emu,
+    /// `None` while playing back a recording.
+    pty: Option<Arc<Mutex<ptycore::PtyHandle>>>,
+    /// Shared with the PTY reader thread, which records output.
+    recorder: Arc<Mutex<Option<Recorder>>>,
+    record_input: bool,
+    player: Option<Player>,
+    play_status: String,
+    last_update: Instant,
+    events: Receiver<EmuEvent>,
+    /// Fed by a parser thread; lock it only for as long as needed.
+    emu: SharedEmu,
     renderer: Renderer,
     theme: theme::Theme,
+    palette: theme::Palette,
     switcher: ThemeSwitcher,
     panels: Panels,
+    bell: Bell,
+    session: config::Session,
     cell_width: f64,
     cell_height: f64,
+    /// Show row arrival times left of the terminal.
+    time_gutter: bool,
+    /// Show the emulator debug inspector over the terminal.
+    inspector: bool,
     scale_factor: f64,
+    focused: bool,
+    blink_epoch: Instant,
+    sync_started: Option<Instant>,
 }
 
 impl State {
-    async fn new(window: &winit::window::Window) -> Result<Self> {
+    async fn new(window: &winit::window::Window, launch: Launch) -> Result<Self> {
         let size = window.inner_size();
         let instance = wgpu::Instance::default();
         let surface = unsafe { instance.create_surface(window)? };
@@ -64,33 +115,114 @@ impl State {
         };
         surface.configure(&device, &config);
 
-        let cols = 80;
-        let rows = 24;
-        let mut handle = spawn_shell(cols, rows, ShellPrefs::default())?;
-        let mut reader = handle.take_reader();
-        let (tx, rx) = unbounded();
-        std::thread::spawn(move || {
-            let mut buf = [0u8; 4096];
-            loop {
-                match reader.read(&mut buf) {
-                    Ok(0) | Err(_) => break,
-                    Ok(n) => {
-                        let _ = tx.send(buf[..n].to_vec());
-                    }
+        let settings = config::load_config().unwrap_or_else(|e| {
+            log::warn!("config: {e:#}");
+            config::Config::default()
+        });
+        let session = if settings.session.restore {
+            load_session()
+        } else {
+            None
+        };
+        let recorder: Arc<Mutex<Option<Recorder>>> = Arc::new(Mutex::new(None));
+        let (tx, rx) = bounded(OUTPUT_QUEUE);
+        let (cols, rows, pty, player, record_input) = match launch {
+            Launch::Shell {
+                command,
+                cwd,
+                record,
+                record_input,
+            } => {
+                let (cols, rows) = (80, 24);
+                let mut prefs = ShellPrefs {
+                    program_version: format!("{}+{}", env!("CARGO_PKG_VERSION"), env!("GIT_SHA")),
+                    ..settings.shell.to_prefs()
+                };
+                if let Some((program, args)) = command.split_first() {
+                    prefs.program = Some(program.clone());
+                    prefs.args = args.to_vec();
                 }
+                // A restored session carries on in the directory it was in,
+                // as last reported through OSC 7, if that still exists.
+                let session_cwd = session
+                    .as_ref()
+                    .and_then(|snap| snap.cwd.as_ref())
+                    .map(PathBuf::from)
+                    .filter(|dir| dir.is_dir());
+                if let Some(dir) = cwd.or(session_cwd) {
+                    prefs.cwd = Some(dir);
+                }
+                let mut handle = spawn_shell(cols, rows, prefs)?;
+                if let Some(path) = record {
+                    let rec = Recorder::create(&path, cols, rows, handle.term(), record_input)?;
+                    *recorder.lock().unwrap() = Some(rec);
+                }
+                let mut reader = handle.take_reader();
+                let rec = recorder.clone();
+                std::thread::spawn(move || {
+                    let mut buf = [0u8; 4096];
+                    loop {
+                        match reader.read(&mut buf) {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => {
+                                if let Some(r) = rec.lock().unwrap().as_mut() {
+                                    if let Err(e) = r.output(&buf[..n]) {
+                                        log::warn!("recording: {e:#}");
+                                    }
+                                }
+                                let _ = tx.send(buf[..n].to_vec());
+                            }
+                        }
+                    }
+                });
+                let pty = Arc::new(Mutex::new(handle));
+                (cols, rows, Some(pty), None, record_input)
             }
-        });
-        let pty = Arc::new(Mutex::new(handle));
+            Launch::Play { path, speed } => {
+                let cast = Cast::load(&path)?;
+                let (cols, rows) = (cast.header.width.max(1), cast.header.height.max(1));
+                (cols, rows, None, Some(Player::new(cast, speed)), false)
+            }
+        };
 
-        let emu = Emu::new(cols as usize, rows as usize);
-        let renderer = Renderer::new();
+        let (ev_tx, events) = unbounded();
+        let mut emu = Emu::new(cols as usize, rows as usize);
+        emu.set_default_cursor_style(settings.cursor.to_style());
+        emu.set_listener(Box::new(ev_tx));
+        emu.scrollback_limit = settings.scrollback.lines;
+        if settings.scrollback.spill_to_disk {
+            let name = format!("scrollback-{}-{}", std::process::id(), unix_time());
+            if let Some(dir) = cache_path(&name) {
+                if let Err(e) = emu.spill_scrollback_to(dir) {
+                    log::warn!("scrollback spill: {e:#}");
+                }
+            }
+        }
+        let mut renderer = Renderer::new();
+        renderer.bidi = settings.bidi.enabled;
         let theme = theme::load_theme("tron")?;
+        let palette = theme::Palette::new(&theme);
         let switcher = ThemeSwitcher::new();
         let panels = Panels::new();
+        let bell = Bell::new(&settings.bell);
 
         let scale_factor = window.scale_factor();
-        let cell_width = size.width as f64 / cols as f64;
+        let time_gutter = settings.scrollback.timestamps;
+        let gutter_cols = if time_gutter { TIME_GUTTER_COLS } else { 0 };
+        let cell_width = size.width as f64 / (cols as usize + gutter_cols) as f64;
         let cell_height = size.height as f64 / rows as f64;
+        emu.allow_window_ops = settings.policy.window_ops;
+        emu.allow_title_reports = settings.policy.title_reports;
+        emu.allow_clipboard_read = settings.policy.clipboard_read;
+        emu.limits = settings.limits;
+        emu.width_policy = settings.unicode;
+        emu.set_cell_size(cell_width.round() as u32, cell_height.round() as u32);
+        if let Some(snap) = &session {
+            restore_session(&mut emu, snap);
+        }
+        let emu = SharedEmu::new(emu);
+        // During playback the sender is already gone and the thread exits.
+        emu.spawn_parser(rx);
 
         Ok(Self {
             surface,
@@ -98,16 +230,29 @@ impl State {
             queue,
             config,
             size,
-            _pty: pty,
-            rx,
+            pty,
+            recorder,
+            record_input,
+            player,
+            play_status: String::new(),
+            last_update: Instant::now(),
+            events,
             emu,
             renderer,
             theme,
+            palette,
             switcher,
             panels,
+            bell,
+            session: settings.session,
             cell_width,
             cell_height,
+            time_gutter,
+            inspector: false,
             scale_factor,
+            focused: true,
+            blink_epoch: Instant::now(),
+            sync_started: None,
         })
     }
 
@@ -118,18 +263,33 @@ impl State {
                 self.scale_factor = sf;
                 self.cell_width *= ratio;
                 self.cell_height *= ratio;
+                self.emu.lock().set_cell_size(
+                    self.cell_width.round() as u32,
+                    self.cell_height.round() as u32,
+                );
             }
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
             self.renderer.resize(new_size.width, new_size.height);
-            let cols = (new_size.width as f64 / self.cell_width).floor().max(1.0) as u16;
+            let width = new_size.width as f64 - self.gutter_width();
+            let cols = (width / self.cell_width).floor().max(1.0) as u16;
             let rows = (new_size.height as f64 / self.cell_height).floor().max(1.0) as u16;
-            if let Ok(mut pty) = self._pty.lock() {
+            // A recording keeps the size it was made at.
+            let Some(pty) = &self.pty else {
+                return;
+            };
+            if let Ok(mut pty) = pty.lock() {
                 let _ = pty.resize(cols, rows);
             }
-            self.emu.resize(cols as usize, rows as usize);
+            let mut emu = self.emu.lock();
+            if (cols as usize, rows as usize) != (emu.cols, emu.rows) {
+                if let Some(r) = self.recorder.lock().unwrap().as_mut() {
+                    let _ = r.resize(cols, rows);
+                }
+            }
+            emu.resize(cols as usize, rows as usize);
         }
     }
 
@@ -137,6 +297,7 @@ impl State {
         match event {
             WindowEvent::KeyboardInput { input, .. } => {
                 if input.state == ElementState::Pressed {
+                    self.blink_epoch = Instant::now();
                     #[allow(deprecated)]
                     if is_toggle_theme(input.modifiers, input.virtual_keycode) {
                         self.switcher.toggle(theme::list_themes);
@@ -160,15 +321,67 @@ impl State {
                         };
                         if let TSAction::Apply(name) = act {
                             if let Ok(th) = theme::load_theme(&name) {
+                                self.palette = theme::Palette::new(&th);
                                 self.theme = th;
                             }
                         }
                         return true;
                     }
-                    if input.virtual_keycode == Some(VirtualKeyCode::F1) {
-                        if let Ok(mut pty) = self._pty.lock() {
-                            let _ = pty.write(b"nmap --version\n");
+                    if self.player.is_some() {
+                        return self.playback_key(input.virtual_keycode);
+                    }
+                    #[allow(deprecated)]
+                    if is_toggle_recording(input.modifiers, input.virtual_keycode) {
+                        self.toggle_recording();
+                        return true;
+                    }
+                    #[allow(deprecated)]
+                    if is_toggle_inspector(input.modifiers, input.virtual_keycode) {
+                        self.inspector = !self.inspector;
+                        let log = if self.inspector { INSPECTOR_LOG } else { 0 };
+                        self.emu.lock().set_trace(log);
+                        return true;
+                    }
+                    #[allow(deprecated)]
+                    if is_toggle_mute(input.modifiers, input.virtual_keycode) {
+                        self.bell.toggle_mute();
+                        return true;
+                    }
+                    #[allow(deprecated)]
+                    if is_save_snapshot(input.modifiers, input.virtual_keycode) {
+                        self.save_snapshot();
+                        return true;
+                    }
+                    #[allow(deprecated)]
+                    if is_export(input.modifiers, input.virtual_keycode) {
+                        #[allow(deprecated)]
+                        let region = if input.modifiers.alt() {
+                            Region::History
+                        } else {
+                            Region::Screen
+                        };
+                        self.export_html(region);
+                        return true;
+                    }
+                    #[allow(deprecated)]
+                    let shift = input.modifiers.shift();
+                    if shift {
+                        let mut emu = self.emu.lock();
+                        let page = emu.rows as isize - 1;
+                        match input.virtual_keycode {
+                            Some(VirtualKeyCode::PageUp) => {
+                                emu.scroll_display(page);
+                                return true;
+                            }
+                            Some(VirtualKeyCode::PageDown) => {
+                                emu.scroll_display(-page);
+                                return true;
+                            }
+                            _ => {}
                         }
+                    }
+                    if input.virtual_keycode == Some(VirtualKeyCode::F1) {
+                        self.send_input(b"nmap --version\n");
                         return true;
                     }
                 }
@@ -178,14 +391,273 @@ impl State {
         }
     }
 
-    fn update(&mut self) {
-        while let Ok(bytes) = self.rx.try_recv() {
-            self.emu.on_bytes(&bytes);
+    /// Space pauses, Left/Right seek 5s, Up/Down double or halve the speed.
+    fn playback_key(&mut self, key: Option<VirtualKeyCode>) -> bool {
+        let Some(player) = self.player.as_mut() else {
+            return false;
+        };
+        match key {
+            Some(VirtualKeyCode::Space) => player.toggle_pause(),
+            Some(VirtualKeyCode::Left) => {
+                player.seek(player.position() - 5.0, &mut self.emu.lock())
+            }
+            Some(VirtualKeyCode::Right) => {
+                player.seek(player.position() + 5.0, &mut self.emu.lock())
+            }
+            Some(VirtualKeyCode::Up) => player.speed = (player.speed * 2.0).min(16.0),
+            Some(VirtualKeyCode::Down) => player.speed = (player.speed / 2.0).max(1.0 / 16.0),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Send what the user typed or pasted to the shell, recording it if
+    /// input recording is on.
+    fn send_input(&self, bytes: &[u8]) {
+        if let Some(r) = self.recorder.lock().unwrap().as_mut() {
+            let _ = r.input(bytes);
+        }
+        self.write_pty(bytes);
+    }
+
+    /// Write to the shell as is. Terminal replies go through here so they
+    /// never end up in a recording as typed input.
+    fn write_pty(&self, bytes: &[u8]) {
+        if let Some(pty) = &self.pty {
+            if let Ok(mut pty) = pty.lock() {
+                let _ = pty.write(bytes);
+            }
+        }
+    }
+
+    /// Start recording to the cache dir, or stop the running recording.
+    fn toggle_recording(&self) {
+        if self.finish_recording() {
+            return;
         }
+        let Some(path) = cache_path(&format!("recording-{}.cast", unix_time())) else {
+            return;
+        };
+        let Some(term) = self.pty.as_ref().and_then(|p| Some(p.lock().ok()?.term())) else {
+            return;
+        };
+        let (cols, rows) = {
+            let emu = self.emu.lock();
+            (emu.cols as u16, emu.rows as u16)
+        };
+        match Recorder::create(&path, cols, rows, term, self.record_input) {
+            Ok(rec) => {
+                *self.recorder.lock().unwrap() = Some(rec);
+                log::info!("recording to {}", path.display());
+            }
+            Err(e) => log::warn!("recording: {e:#}"),
+        }
+    }
+
+    /// Flush and close the running recording. Returns whether one was running.
+    fn finish_recording(&self) -> bool {
+        let Some(rec) = self.recorder.lock().unwrap().take() else {
+            return false;
+        };
+        match rec.finish() {
+            Ok(()) => log::info!("recording stopped"),
+            Err(e) => log::warn!("recording: {e:#}"),
+        }
+        true
+    }
+
+    fn update(&mut self, window: &winit::window::Window) {
+        let now = Instant::now();
+        let elapsed = now - self.last_update;
+        self.last_update = now;
+        if let Some(player) = self.player.as_mut() {
+            player.advance(elapsed, &mut self.emu.lock());
+            let status = format!(
+                "{} {}/{} x{}",
+                if player.paused { "paused" } else { "playing" },
+                clock(player.position()),
+                clock(player.duration()),
+                player.speed
+            );
+            if status != self.play_status {
+                self.play_status = status;
+                self.refresh_title(window);
+            }
+        }
+        self.emu.lock().tick();
+        while let Ok(event) = self.events.try_recv() {
+            self.handle_event(event, window);
+        }
+        let synchronized = self.emu.lock().synchronized;
+        self.sync_started = match (synchronized, self.sync_started) {
+            (true, None) => Some(Instant::now()),
+            (true, started) => started,
+            (false, _) => None,
+        };
         self.panels.tick();
     }
 
+    fn handle_event(&mut self, event: EmuEvent, window: &winit::window::Window) {
+        match event {
+            EmuEvent::Title(_) | EmuEvent::Progress(_) => self.refresh_title(window),
+            EmuEvent::Bell => {
+                if let Some(ring) = self.bell.ring(self.focused) {
+                    if ring.sound {
+                        bell::play_alarm();
+                    }
+                    if ring.urgent {
+                        window.request_user_attention(Some(UserAttentionType::Informational));
+                    }
+                }
+            }
+            EmuEvent::PtyWrite(bytes) => self.write_pty(&bytes),
+            EmuEvent::ClipboardStore { .. } | EmuEvent::ClipboardLoad { .. } => {
+                log::debug!("OSC 52 ignored: no clipboard backend");
+            }
+            EmuEvent::WindowOp(op) => self.window_op(op, window),
+            EmuEvent::Cwd(_) | EmuEvent::PromptMark(_) => {}
+        }
+    }
+
+    /// Write the exact terminal state to the cache dir, e.g. to attach to a
+    /// bug report.
+    fn save_snapshot(&self) {
+        let stamp = unix_time();
+        let Some(path) = cache_path(&format!("snapshot-{stamp}.json")) else {
+            return;
+        };
+        match self.write_snapshot(&path) {
+            Ok(()) => log::info!("snapshot saved to {}", path.display()),
+            Err(e) => log::warn!("snapshot: {e:#}"),
+        }
+    }
+
+    /// Save `region` as themed HTML in the cache dir.
+    fn export_html(&self, region: Region) {
+        let stamp = unix_time();
+        let Some(path) = cache_path(&format!("export-{stamp}.html")) else {
+            return;
+        };
+        let options = export::Options {
+            times: self.time_gutter,
+        };
+        let html = export::export_with(
+            &self.emu.lock(),
+            region,
+            Format::Html,
+            &self.palette,
+            options,
+        );
+        match std::fs::write(&path, html) {
+            Ok(()) => log::info!("exported to {}", path.display()),
+            Err(e) => log::warn!("export: {e}"),
+        }
+    }
+
+    fn write_snapshot(&self, path: &std::path::Path) -> Result<()> {
+        let snap = self
+            .emu
+            .lock()
+            .snapshot(self.session.scrollback_kib * 1024)?;
+        snap.save(path)
+    }
+
+    /// Called on exit when session restore is enabled.
+    fn save_session(&self) {
+        if !self.session.restore {
+            return;
+        }
+        if let Some(path) = cache_path("session.json") {
+            if let Err(e) = self.write_snapshot(&path) {
+                log::warn!("session: {e:#}");
+            }
+        }
+    }
+
+    /// Window title: the application's OSC title plus any OSC 9;4 progress.
+    fn refresh_title(&self, window: &winit::window::Window) {
+        let emu = self.emu.lock();
+        let title = if emu.title.is_empty() {
+            "terminal-ui"
+        } else {
+            emu.title.as_str()
+        };
+        let p = emu.progress;
+        let suffix = match p.state {
+            ProgressState::None => String::new(),
+            ProgressState::Normal => format!(" [{}%]", p.percent),
+            ProgressState::Error => format!(" [error {}%]", p.percent),
+            ProgressState::Indeterminate => " [busy]".to_string(),
+            ProgressState::Paused => format!(" [paused {}%]", p.percent),
+        };
+        let playback = if self.player.is_some() {
+            format!(" [{}]", self.play_status)
+        } else {
+            String::new()
+        };
+        window.set_title(&format!("{title}{suffix}{playback}"));
+    }
+
+    fn window_op(&self, op: WindowOp, window: &winit::window::Window) {
+        let cells_to_px = |cols: f64, rows: f64| {
+            PhysicalSize::new(
+                (cols * self.cell_width + self.gutter_width()).round() as u32,
+                (rows * self.cell_height).round() as u32,
+            )
+        };
+        match op {
+            WindowOp::Deiconify => window.set_minimized(false),
+            WindowOp::Iconify => window.set_minimized(true),
+            WindowOp::Move { x, y } => window.set_outer_position(PhysicalPosition::new(x, y)),
+            WindowOp::ResizePixels { width, height } => {
+                let cur = window.inner_size();
+                let w = width.unwrap_or(cur.width);
+                let h = height.unwrap_or(cur.height);
+                window.set_inner_size(PhysicalSize::new(w, h));
+            }
+            WindowOp::ResizeCells { cols, rows } => {
+                let (cur_cols, cur_rows) = {
+                    let emu = self.emu.lock();
+                    (emu.cols, emu.rows)
+                };
+                let cols = cols.map_or(cur_cols, usize::from) as f64;
+                let rows = rows.map_or(cur_rows, usize::from) as f64;
+                window.set_inner_size(cells_to_px(cols, rows));
+            }
+            WindowOp::Raise => window.focus_window(),
+            WindowOp::Lower => log::debug!("XTWINOPS lower: not supported by winit"),
+            WindowOp::Maximize(on) => window.set_maximized(on),
+            WindowOp::Fullscreen(on) => {
+                let on = on.unwrap_or(window.fullscreen().is_none());
+                window.set_fullscreen(on.then_some(Fullscreen::Borderless(None)));
+            }
+        }
+    }
+
+    /// Pixels taken by the timestamp gutter, if shown.
+    fn gutter_width(&self) -> f64 {
+        if self.time_gutter {
+            TIME_GUTTER_COLS as f64 * self.cell_width
+        } else {
+            0.0
+        }
+    }
+
+    fn cursor_shown(&self, emu: &Emu) -> bool {
+        if !emu.cursor_visible {
+            return false;
+        }
+        if !self.focused || !emu.cursor_style.blinking {
+            return true;
+        }
+        let phase = self.blink_epoch.elapsed().as_millis() / CURSOR_BLINK_INTERVAL.as_millis();
+        phase % 2 == 0
+    }
+
     fn render(&mut self) -> Result<(), SurfaceError> {
+        if matches!(self.sync_started, Some(t) if t.elapsed() < SYNC_UPDATE_TIMEOUT) {
+            return Ok(());
+        }
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
@@ -213,6 +685,75 @@ impl State {
             .draw_neon_grid(&mut encoder, self.size.width, self.size.height, &self.theme);
         self.renderer
             .draw_scanlines(&mut encoder, self.size.width, self.size.height, &self.theme);
+        // The parser waits while we hold this; release it before the GPU work.
+        let emu = self.emu.lock();
+        let x0 = self.gutter_width();
+        if self.time_gutter {
+            self.renderer.draw_time_gutter(
+                &mut encoder,
+                &emu,
+                self.cell_width as f32,
+                self.cell_height as f32,
+                &self.theme.ui.text,
+            );
+        }
+        self.renderer.draw_grid(
+            &mut encoder,
+            &emu,
+            &self.palette,
+            x0 as f32,
+            self.cell_width as f32,
+            self.cell_height as f32,
+        );
+        if self.cursor_shown(&emu) && emu.display_offset == 0 {
+            let line_cols = emu.line_cols(emu.cur_y);
+            let scale_x = if line_cols < emu.cols { 2.0 } else { 1.0 };
+            let mut cur_x = emu.cur_x.min(line_cols - 1);
+            if self.renderer.bidi {
+                let row = &emu.grid[emu.cur_y][..line_cols];
+                if let Some(layout) = bidi::layout(&emu, row) {
+                    cur_x = layout.visual_col(cur_x);
+                }
+            }
+            self.renderer.draw_cursor(
+                &mut encoder,
+                (x0 + cur_x as f64 * self.cell_width * scale_x) as f32,
+                (emu.cur_y as f64 * self.cell_height) as f32,
+                (self.cell_width * scale_x) as f32,
+                self.cell_height as f32,
+                emu.cursor_style,
+                &self.theme.terminal.cursor,
+                self.focused,
+            );
+        }
+        let progress = emu.progress;
+        let inspector = self.inspector.then(|| inspect(&emu));
+        drop(emu);
+        if let Some(lines) = inspector {
+            self.renderer.draw_inspector(
+                &mut encoder,
+                x0 as f32 + 24.0,
+                24.0,
+                (self.size.width as f32 - x0 as f32 - 48.0).min(640.0),
+                self.size.height as f32 - 48.0,
+                self.cell_height as f32,
+                &lines,
+                &self.theme,
+            );
+        }
+        let flash = self.bell.flash_alpha();
+        if flash > 0.0 {
+            self.renderer.draw_rounded_rect(
+                &mut encoder,
+                0.0,
+                0.0,
+                self.size.width as f32,
+                self.size.height as f32,
+                &format!("rgba(255,255,255,{:.3})", flash * 0.18),
+                "",
+                0.0,
+            );
+        }
         let pw = 320.0;
         let px = self.size.width as f32 - pw - 24.0;
         self.renderer
@@ -237,6 +778,28 @@ impl State {
             "RAM",
             &self.theme,
         );
+        if progress.state != ProgressState::None {
+            let (value, label) = match progress.state {
+                // Sweep back and forth when there is no real value.
+                ProgressState::Indeterminate => {
+                    let t = self.blink_epoch.elapsed().as_secs_f32();
+                    ((t * 2.0).sin() * 0.5 + 0.5, "JOB")
+                }
+                ProgressState::Error => (progress.percent as f32 / 100.0, "JOB ERROR"),
+                ProgressState::Paused => (progress.percent as f32 / 100.0, "JOB PAUSED"),
+                _ => (progress.percent as f32 / 100.0, "JOB"),
+            };
+            self.renderer.draw_bar(
+                &mut encoder,
+                px + 24.0,
+                160.0,
+                pw - 48.0,
+                16.0,
+                value,
+                label,
+                &self.theme,
+            );
+        }
         if self.switcher.is_open() {
             let (w, h) = (self.size.width as f32, self.size.height as f32);
             let layout = self.switcher.layout(w, h);
@@ -257,17 +820,88 @@ fn is_toggle_theme(mods: winit::event::ModifiersState, key: Option<VirtualKeyCod
     key == Some(VirtualKeyCode::T) && (mods.ctrl() || mods.logo()) && mods.shift()
 }
 
+fn is_toggle_recording(mods: winit::event::ModifiersState, key: Option<VirtualKeyCode>) -> bool {
+    key == Some(VirtualKeyCode::R) && (mods.ctrl() || mods.logo()) && mods.shift()
+}
+
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// `m:ss` for playback status.
+fn clock(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn is_export(mods: winit::event::ModifiersState, key: Option<VirtualKeyCode>) -> bool {
+    key == Some(VirtualKeyCode::E) && (mods.ctrl() || mods.logo()) && mods.shift()
+}
+
+fn is_save_snapshot(mods: winit::event::ModifiersState, key: Option<VirtualKeyCode>) -> bool {
+    key == Some(VirtualKeyCode::S) && (mods.ctrl() || mods.logo()) && mods.shift()
+}
+
+/// `<cache_dir>/terminal-ui/<name>`, creating the directory.
+fn cache_path(name: &str) -> Option<PathBuf> {
+    let dir = dirs::cache_dir()?.join("terminal-ui");
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        log::warn!("cannot create {}: {e}", dir.display());
+        return None;
+    }
+    Some(dir.join(name))
+}
+
+/// The session saved on the last exit, if any.
+fn load_session() -> Option<Snapshot> {
+    let path = cache_path("session.json")?;
+    if !path.exists() {
+        return None;
+    }
+    Snapshot::load(&path)
+        .map_err(|e| log::warn!("session: {e:#}"))
+        .ok()
+}
+
+/// Bring back the previous session's screen and scrollback; the new shell
+/// starts on a fresh line below it.
+fn restore_session(emu: &mut Emu, snap: &Snapshot) {
+    match emu.restore(snap) {
+        Ok(()) => {
+            if emu.cur_x > 0 {
+                emu.on_bytes(b"\r\n");
+            }
+        }
+        Err(e) => log::warn!("session: {e:#}"),
+    }
+}
+
+fn is_toggle_inspector(mods: winit::event::ModifiersState, key: Option<VirtualKeyCode>) -> bool {
+    key == Some(VirtualKeyCode::D) && (mods.ctrl() || mods.logo()) && mods.shift()
+}
+
+fn is_toggle_mute(mods: winit::event::ModifiersState, key: Option<VirtualKeyCode>) -> bool {
+    key == Some(VirtualKeyCode::M) && (mods.ctrl() || mods.logo()) && mods.shift()
+}
+
 fn main() -> Result<()> {
     if std::env::args().any(|a| a == "--version") {
         println!("{} ({})", env!("CARGO_PKG_VERSION"), env!("GIT_SHA"));
         return Ok(());
     }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("export") {
+        return export_cli(&args[1..]);
+    }
+    let launch = parse_launch(&args)?;
     env_logger::init();
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_decorations(false)
         .build(&event_loop)?;
-    let mut state = pollster::block_on(State::new(&window))?;
+    let mut state = pollster::block_on(State::new(&window, launch))?;
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -275,7 +909,21 @@ fn main() -> Result<()> {
             Event::WindowEvent { event, window_id } if window_id == window.id() => {
                 if !state.input(&event) {
                     match event {
-                        WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                        WindowEvent::CloseRequested => {
+                            state.finish_recording();
+                            state.save_session();
+                            // The parser thread keeps the emulator alive past
+                            // the window, so clean up the pages here.
+                            state.emu.lock().discard_spilled_scrollback();
+                            *control_flow = ControlFlow::Exit;
+                        }
+                        WindowEvent::Focused(focused) => {
+                            if focused {
+                                window.request_user_attention(None);
+                            }
+                            state.focused = focused;
+                            state.blink_epoch = Instant::now();
+                        }
                         WindowEvent::Resized(size) => state.resize(size, None),
                         WindowEvent::ScaleFactorChanged {
                             new_inner_size,
@@ -287,7 +935,7 @@ fn main() -> Result<()> {
                 }
             }
             Event::RedrawRequested(_) => {
-                state.update();
+                state.update(&window);
                 match state.render() {
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::Lost) => state.resize(state.size, None),
@@ -302,3 +950,91 @@ fn main() -> Result<()> {
         }
     });
 }
+
+/// `app [--cwd DIR] [--record FILE [--record-input]] [-- PROGRAM ARGS...]` or
+/// `app play FILE [--speed N]`.
+fn parse_launch(args: &[String]) -> Result<Launch> {
+    let mut it = args.iter();
+    if args.first().map(String::as_str) == Some("play") {
+        it.next();
+        let mut path = None;
+        let mut speed: f64 = 1.0;
+        while let Some(arg) = it.next() {
+            match arg.as_str() {
+                "--speed" => {
+                    let v = it.next().context("--speed needs a value")?;
+                    speed = v.parse().with_context(|| format!("bad speed {v:?}"))?;
+                }
+                _ if path.is_none() && !arg.starts_with("--") => path = Some(PathBuf::from(arg)),
+                _ => anyhow::bail!("unexpected argument {arg:?}"),
+            }
+        }
+        let path = path.context("usage: app play <file.cast> [--speed N]")?;
+        if speed.is_nan() || speed <= 0.0 {
+            anyhow::bail!("speed must be positive");
+        }
+        return Ok(Launch::Play { path, speed });
+    }
+    let mut record = None;
+    let mut record_input = false;
+    let mut cwd = None;
+    let mut command = Vec::new();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--record" => record = Some(PathBuf::from(it.next().context("--record needs a file")?)),
+            "--record-input" => record_input = true,
+            "--cwd" => cwd = Some(PathBuf::from(it.next().context("--cwd needs a directory")?)),
+            "--" => {
+                command = it.by_ref().cloned().collect();
+                anyhow::ensure!(!command.is_empty(), "`--` needs a program to run");
+            }
+            // Unknown flags are left for the platform (e.g. `-psn_*` on macOS).
+            _ => {}
+        }
+    }
+    Ok(Launch::Shell {
+        command,
+        cwd,
+        record,
+        record_input,
+    })
+}
+
+/// `app export <snapshot.json> [--format text|ansi|html|svg]
+/// [--region screen|history] [--theme NAME] [--out FILE]`
+///
+/// Renders a snapshot saved with Ctrl/Cmd+Shift+S without opening a window.
+fn export_cli(args: &[String]) -> Result<()> {
+    let mut input = None;
+    let mut format = Format::Text;
+    let mut region = Region::History;
+    let mut theme_name = "tron".to_string();
+    let mut out = None;
+    let mut options = export::Options::default();
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().with_context(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--format" => format = value()?.parse()?,
+            "--region" => region = value()?.parse()?,
+            "--theme" => theme_name = value()?.clone(),
+            "--out" => out = Some(PathBuf::from(value()?)),
+            "--times" => options.times = true,
+            _ if input.is_none() && !arg.starts_with("--") => input = Some(PathBuf::from(arg)),
+            _ => anyhow::bail!("unexpected argument {arg:?}"),
+        }
+    }
+    let input =
+        input.context("usage: app export <snapshot.json> [--format F] [--times] [--out FILE]")?;
+    let snap = Snapshot::load(&input)?;
+    let mut emu = Emu::new(snap.cols, snap.rows);
+    emu.scrollback_limit = snap.scrollback.rows;
+    emu.restore(&snap)?;
+    let palette = theme::Palette::new(&theme::load_theme(&theme_name)?);
+    let text = export::export_with(&emu, region, format, &palette, options);
+    match out {
+        Some(path) => std::fs::write(&path, text).with_context(|| path.display().to_string())?,
+        None => print!("{text}"),
+    }
+    Ok(())
+}
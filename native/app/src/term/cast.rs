@@ -0,0 +1,370 @@
+//! asciicast v2 recording and playback.
+//!
+//! A recording is a JSON header line followed by one `[time, code, data]`
+//! array per event: `"o"` output, `"i"` input and `"r"` resize
+//! (`"COLSxROWS"`). See <https://docs.asciinema.org/manual/asciicast/v2/>.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::emu::Snapshot;
+use super::Emu;
+
+/// Output replayed between two playback checkpoints.
+const CHECKPOINT_BYTES: usize = 1 << 20;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventData {
+    Output(String),
+    Input(String),
+    Resize { cols: u16, rows: u16 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    /// Seconds since the start of the recording.
+    pub time: f64,
+    pub data: EventData,
+}
+
+/// Writes an asciicast v2 file. Output arrives in arbitrary PTY-sized
+/// chunks, so an incomplete UTF-8 sequence at the end of a chunk is held
+/// back until the rest of it is read.
+pub struct Recorder {
+    out: BufWriter<File>,
+    start: Instant,
+    pending: Vec<u8>,
+    record_input: bool,
+}
+
+impl Recorder {
+    /// `term` is the `TERM` the recorded session runs with, which is not
+    /// necessarily ours.
+    pub fn create(
+        path: &Path,
+        cols: u16,
+        rows: u16,
+        term: &str,
+        record_input: bool,
+    ) -> Result<Self> {
+        let file = File::create(path).with_context(|| path.display().to_string())?;
+        let mut env = BTreeMap::new();
+        if let Ok(shell) = std::env::var("SHELL") {
+            env.insert("SHELL".to_string(), shell);
+        }
+        env.insert("TERM".to_string(), term.to_string());
+        let header = Header {
+            version: 2,
+            width: cols,
+            height: rows,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs()),
+            title: None,
+            env,
+        };
+        let mut out = BufWriter::new(file);
+        serde_json::to_writer(&mut out, &header)?;
+        out.write_all(b"\n")?;
+        Ok(Self {
+            out,
+            start: Instant::now(),
+            pending: Vec::new(),
+            record_input,
+        })
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> Result<()> {
+        let t = self.start.elapsed().as_secs_f64();
+        serde_json::to_writer(&mut self.out, &(round_time(t), code, data))?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn output(&mut self, bytes: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(bytes);
+        let text = take_utf8(&mut self.pending);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.event("o", &text)
+    }
+
+    /// Recorded only when the recorder was created with `record_input`.
+    pub fn input(&mut self, bytes: &[u8]) -> Result<()> {
+        if !self.record_input {
+            return Ok(());
+        }
+        self.event("i", &String::from_utf8_lossy(bytes))
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        self.event("r", &format!("{cols}x{rows}"))
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if !self.pending.is_empty() {
+            let rest = String::from_utf8_lossy(&self.pending).into_owned();
+            self.event("o", &rest)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Microsecond precision is plenty and keeps the files short.
+fn round_time(t: f64) -> f64 {
+    (t * 1e6).round() / 1e6
+}
+
+/// Remove and return the longest decodable prefix of `buf`. Invalid bytes
+/// become U+FFFD; a truncated sequence at the end is left in `buf`.
+fn take_utf8(buf: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    let mut rest: &[u8] = buf;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(s) => {
+                text.push_str(s);
+                rest = &[];
+                break;
+            }
+            Err(e) => {
+                let (good, bad) = rest.split_at(e.valid_up_to());
+                text.push_str(std::str::from_utf8(good).unwrap_or_default());
+                match e.error_len() {
+                    Some(n) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        rest = &bad[n..];
+                    }
+                    None => {
+                        rest = bad;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    let keep = rest.len();
+    buf.drain(..buf.len() - keep);
+    text
+}
+
+/// A parsed recording.
+#[derive(Clone, Debug)]
+pub struct Cast {
+    pub header: Header,
+    pub events: Vec<Event>,
+}
+
+impl Cast {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| path.display().to_string())?;
+        Self::parse(BufReader::new(file))
+    }
+
+    /// Unknown event codes (markers etc.) are skipped.
+    pub fn parse(reader: impl BufRead) -> Result<Self> {
+        let mut lines = reader.lines();
+        let first = lines.next().context("empty recording")??;
+        let header: Header = serde_json::from_str(&first).context("asciicast header")?;
+        if header.version != 2 {
+            bail!("unsupported asciicast version {}", header.version);
+        }
+        let mut events = Vec::new();
+        for (n, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (time, code, data): (f64, String, String) = serde_json::from_str(&line)
+                .with_context(|| format!("asciicast event on line {}", n + 2))?;
+            let data = match code.as_str() {
+                "o" => EventData::Output(data),
+                "i" => EventData::Input(data),
+                "r" => {
+                    let Some((c, r)) = data.split_once('x') else {
+                        bail!("bad resize {data:?} on line {}", n + 2);
+                    };
+                    EventData::Resize {
+                        cols: c.parse()?,
+                        rows: r.parse()?,
+                    }
+                }
+                _ => continue,
+            };
+            events.push(Event { time, data });
+        }
+        Ok(Self { header, events })
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.events.last().map_or(0.0, |e| e.time)
+    }
+}
+
+/// Replays a [`Cast`] into an [`Emu`] on the wall clock, with speed control,
+/// pause and seek. No shell is involved.
+pub struct Player {
+    cast: Cast,
+    /// Playback position in recording time.
+    pos: f64,
+    /// Index of the next event to apply.
+    next: usize,
+    pub speed: f64,
+    pub paused: bool,
+    /// Emulator state taken every [`CHECKPOINT_BYTES`] of output, in event
+    /// order, so a seek replays from the nearest one instead of the start.
+    checkpoints: Vec<Checkpoint>,
+    /// Output applied since the last checkpoint.
+    since_checkpoint: usize,
+}
+
+struct Checkpoint {
+    /// `Player::next` when it was taken.
+    next: usize,
+    /// Time of the last event applied before it.
+    time: f64,
+    snapshot: Snapshot,
+}
+
+impl Player {
+    pub fn new(cast: Cast, speed: f64) -> Self {
+        Self {
+            cast,
+            pos: 0.0,
+            next: 0,
+            speed,
+            paused: false,
+            checkpoints: Vec::new(),
+            since_checkpoint: 0,
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.cast.header
+    }
+
+    pub fn position(&self) -> f64 {
+        self.pos
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.cast.duration()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.cast.events.len()
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Move the clock forward by `elapsed` wall time and apply what is due.
+    pub fn advance(&mut self, elapsed: Duration, emu: &mut Emu) {
+        if self.paused {
+            return;
+        }
+        let target = self.pos + elapsed.as_secs_f64() * self.speed;
+        self.play_until(target, emu);
+    }
+
+    /// Jump to `time` seconds. Playback resumes from the latest checkpoint
+    /// before `time` when that is closer than where it is now; going
+    /// backwards with none to use replays from the start into a reset
+    /// emulator.
+    pub fn seek(&mut self, time: f64, emu: &mut Emu) {
+        let time = time.clamp(0.0, self.duration());
+        let back = time < self.pos;
+        let nearest = self
+            .checkpoints
+            .iter()
+            .rev()
+            .find(|c| c.time <= time)
+            .filter(|c| back || c.next > self.next);
+        if let Some(c) = nearest {
+            emu.resize(c.snapshot.cols, c.snapshot.rows);
+        }
+        match nearest {
+            Some(c) if emu.restore(&c.snapshot).is_ok() => {
+                (self.next, self.pos) = (c.next, c.time);
+                self.since_checkpoint = 0;
+            }
+            _ if back => self.rewind(emu),
+            _ => {}
+        }
+        self.play_until(time, emu);
+    }
+
+    fn rewind(&mut self, emu: &mut Emu) {
+        emu.resize(
+            self.cast.header.width as usize,
+            self.cast.header.height as usize,
+        );
+        emu.on_bytes(b"\x1bc\x1b[3J");
+        emu.title.clear();
+        self.pos = 0.0;
+        self.next = 0;
+        self.since_checkpoint = 0;
+    }
+
+    fn play_until(&mut self, time: f64, emu: &mut Emu) {
+        while let Some(event) = self.cast.events.get(self.next) {
+            if event.time > time {
+                break;
+            }
+            match &event.data {
+                EventData::Output(s) => {
+                    // Rows carry the time they were recorded at, when known.
+                    let start = self.cast.header.timestamp;
+                    emu.set_clock(start.map(|t| t * 1000 + (event.time * 1000.0) as u64));
+                    emu.on_bytes(s.as_bytes());
+                    self.since_checkpoint += s.len();
+                }
+                EventData::Resize { cols, rows } => emu.resize(*cols as usize, *rows as usize),
+                EventData::Input(_) => {}
+            }
+            self.next += 1;
+            self.checkpoint(emu);
+        }
+        self.pos = time.min(self.duration());
+    }
+
+    /// Take a checkpoint if enough output went by since the last one and
+    /// the parser is not in the middle of a sequence.
+    fn checkpoint(&mut self, emu: &Emu) {
+        let seen = self.checkpoints.last().map_or(0, |c| c.next);
+        if self.since_checkpoint < CHECKPOINT_BYTES || self.next <= seen || !emu.is_idle() {
+            return;
+        }
+        if let Ok(snapshot) = emu.snapshot(usize::MAX) {
+            self.checkpoints.push(Checkpoint {
+                next: self.next,
+                time: self.cast.events[self.next - 1].time,
+                snapshot,
+            });
+            self.since_checkpoint = 0;
+        }
+    }
+}
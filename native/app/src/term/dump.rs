@@ -0,0 +1,126 @@
+//! Stable textual dump of the emulator state, used by the golden-file tests
+//! and handy when debugging a misbehaving application by hand.
+
+use std::fmt::Write as _;
+
+use super::emu::{Color, Emu, Flags, LineAttr, Style, Underline};
+
+/// Render grid text, non-default attribute runs, line attributes, cursor and
+/// modes as plain text. The format is line oriented so diffs stay readable:
+///
+/// ```text
+/// size 20x4
+/// cursor 5,1 visible block blink
+/// modes autowrap region=0..3
+/// |hello               |
+/// ...
+/// attr 0 0..5 fg=1 bold
+/// line 2 double-width
+/// ```
+pub fn dump(emu: &Emu) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "size {}x{}", emu.cols, emu.rows);
+    let _ = writeln!(
+        out,
+        "cursor {},{} {} {}{}",
+        emu.cur_x,
+        emu.cur_y,
+        if emu.cursor_visible {
+            "visible"
+        } else {
+            "hidden"
+        },
+        format!("{:?}", emu.cursor_style.shape).to_lowercase(),
+        if emu.cursor_style.blinking {
+            " blink"
+        } else {
+            ""
+        },
+    );
+    let mut modes = Vec::new();
+    if emu.autowrap {
+        modes.push("autowrap".to_string());
+    }
+    if emu.origin_mode {
+        modes.push("origin".to_string());
+    }
+    if emu.insert_mode {
+        modes.push("insert".to_string());
+    }
+    modes.push(format!("region={}..{}", emu.scroll_top, emu.scroll_bottom));
+    let _ = writeln!(out, "modes {}", modes.join(" "));
+    if !emu.title.is_empty() {
+        let _ = writeln!(out, "title {:?}", emu.title);
+    }
+    if !emu.scrollback.is_empty() {
+        let _ = writeln!(out, "scrollback {}", emu.scrollback.len());
+    }
+    for y in 0..emu.rows {
+        let text: String = (0..emu.cols).map(|x| emu.cell(x, y).ch).collect();
+        let _ = writeln!(out, "|{text}|");
+    }
+    for y in 0..emu.rows {
+        let mut x = 0;
+        while x < emu.cols {
+            let style = describe(emu.cell_style(x, y));
+            let start = x;
+            while x < emu.cols && describe(emu.cell_style(x, y)) == style {
+                x += 1;
+            }
+            if !style.is_empty() {
+                let _ = writeln!(out, "attr {y} {start}..{x} {style}");
+            }
+        }
+    }
+    for (y, attr) in emu.line_attrs.iter().enumerate() {
+        let name = match attr {
+            LineAttr::Normal => continue,
+            LineAttr::DoubleWidth => "double-width",
+            LineAttr::DoubleHeightTop => "double-height-top",
+            LineAttr::DoubleHeightBottom => "double-height-bottom",
+        };
+        let _ = writeln!(out, "line {y} {name}");
+    }
+    out
+}
+
+/// Space separated list of everything in `style`; empty for the default.
+pub(super) fn describe(style: &Style) -> String {
+    let mut parts = Vec::new();
+    if style.fg != Color::Default {
+        parts.push(format!("fg={}", color(style.fg)));
+    }
+    if style.bg != Color::Default {
+        parts.push(format!("bg={}", color(style.bg)));
+    }
+    const FLAGS: [(Flags, &str); 8] = [
+        (Flags::BOLD, "bold"),
+        (Flags::DIM, "dim"),
+        (Flags::ITALIC, "italic"),
+        (Flags::BLINK, "blink"),
+        (Flags::INVERSE, "inverse"),
+        (Flags::HIDDEN, "hidden"),
+        (Flags::STRIKE, "strike"),
+        (Flags::PROTECTED, "protected"),
+    ];
+    for (flag, name) in FLAGS {
+        if style.flags.contains(flag) {
+            parts.push(name.to_string());
+        }
+    }
+    if style.underline != Underline::None {
+        parts.push(format!("ul={:?}", style.underline).to_lowercase());
+    }
+    if style.ul != Color::Default {
+        parts.push(format!("ulc={}", color(style.ul)));
+    }
+    parts.join(" ")
+}
+
+fn color(c: Color) -> String {
+    match c {
+        Color::Default => "default".to_string(),
+        Color::Indexed(i) => i.to_string(),
+        Color::Rgb(r, g, b) => format!("#{r:02x}{g:02x}{b:02x}"),
+    }
+}
@@ -1,38 +1,302 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::cmp::{max, min};
-use unicode_width::UnicodeWidthChar;
+use std::mem;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 use vte::{Params, Parser, Perform};
 
-#[allow(dead_code)]
-#[derive(Clone, Copy, Default)]
+use super::event::{
+    Clipboard, EmuEvent, EventListener, NoopListener, Progress, ProgressState, PromptMark, WindowOp,
+};
+
+mod grid;
+mod history;
+mod limits;
+mod rect;
+mod snapshot;
+mod style;
+mod trace;
+mod width;
+
+pub use grid::Grid;
+pub use history::History;
+pub use limits::Limits;
+use limits::{Step, Strings};
+pub use snapshot::Snapshot;
+use style::Styles;
+pub use trace::Unknown;
+use trace::{csi_text, esc_text, osc_text, SeqKind, Trace};
+pub use width::{WidthPolicy, VS15, VS16};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Rgba(pub u8, pub u8, pub u8, pub u8);
 
-#[allow(dead_code)]
-#[derive(Clone, Copy)]
+impl Rgba {
+    pub fn to_css(self) -> String {
+        format!(
+            "rgba({},{},{},{:.3})",
+            self.0,
+            self.1,
+            self.2,
+            self.3 as f32 / 255.0
+        )
+    }
+}
+
+/// A cell color as the application asked for it. Resolved against the
+/// active theme's palette only when drawing, so theme switches recolor
+/// everything already on screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Color {
+    #[default]
+    Default,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// SGR 4 / 4:x / 21 underline style.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Underline {
+    #[default]
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+/// SGR rendition bits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Flags(u16);
+
+impl Flags {
+    pub const BOLD: Flags = Flags(1 << 0);
+    pub const DIM: Flags = Flags(1 << 1);
+    pub const ITALIC: Flags = Flags(1 << 2);
+    pub const BLINK: Flags = Flags(1 << 3);
+    pub const INVERSE: Flags = Flags(1 << 4);
+    pub const HIDDEN: Flags = Flags(1 << 5);
+    pub const STRIKE: Flags = Flags(1 << 6);
+    /// DECSCA: immune to selective erase.
+    pub const PROTECTED: Flags = Flags(1 << 7);
+
+    pub fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn set(&mut self, other: Flags, on: bool) {
+        if on {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+}
+
+/// Everything about a cell except its character.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    #[serde(skip_serializing_if = "is_default")]
+    pub fg: Color,
+    #[serde(skip_serializing_if = "is_default")]
+    pub bg: Color,
+    #[serde(skip_serializing_if = "is_default")]
+    pub flags: Flags,
+    #[serde(skip_serializing_if = "is_default")]
+    pub underline: Underline,
+    /// SGR 58 underline color; `Default` means "same as the foreground".
+    #[serde(skip_serializing_if = "is_default")]
+    pub ul: Color,
+}
+
+fn is_default<T: Default + PartialEq>(v: &T) -> bool {
+    *v == T::default()
+}
+
+/// Index into the emulator's style table; look it up with [`Emu::style`].
+/// Only meaningful for the `Emu` whose cells carry it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StyleId(u32);
+
+impl StyleId {
+    /// Always `Style::default()`.
+    pub const DEFAULT: StyleId = StyleId(0);
+}
+
+/// A character and its interned style: 8 bytes, so a 200x50 screen plus
+/// 10k rows of scrollback stays around 16 MB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Cell {
     pub ch: char,
-    pub fg: Rgba,
-    pub bg: Rgba,
+    pub style: StyleId,
 }
 
 impl Default for Cell {
+    fn default() -> Self {
+        Cell::BLANK
+    }
+}
+
+impl Cell {
+    pub const BLANK: Cell = Cell {
+        ch: ' ',
+        style: StyleId::DEFAULT,
+    };
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorShape {
+    #[default]
+    Block,
+    Underline,
+    Bar,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+impl Default for CursorStyle {
     fn default() -> Self {
         Self {
-            ch: ' ',
-            fg: Rgba(0xD6, 0xEF, 0xFF, 0xFF),
-            bg: Rgba(0x07, 0x12, 0x1A, 0xFF),
+            shape: CursorShape::Block,
+            blinking: true,
         }
     }
 }
 
+/// Per-row rendering attribute set by DECDWL/DECDHL/DECSWL.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineAttr {
+    #[default]
+    Normal,
+    DoubleWidth,
+    DoubleHeightTop,
+    DoubleHeightBottom,
+}
+
+impl LineAttr {
+    pub fn is_double(self) -> bool {
+        self != LineAttr::Normal
+    }
+}
+
+/// SCS designations for G0–G3 and the set shifted in by SO/SI. Only
+/// US-ASCII (`B`) is drawn as such; other sets are recorded so the inspector
+/// can show what the application asked for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Charsets {
+    pub g: [char; 4],
+    /// Index into `g` of the set in GL: 0 after SI, 1 after SO.
+    pub gl: usize,
+}
+
+impl Default for Charsets {
+    fn default() -> Self {
+        Self { g: ['B'; 4], gl: 0 }
+    }
+}
+
+/// State stored by DECSC and restored by DECRC.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct SavedCursor {
+    x: usize,
+    y: usize,
+    pen: Style,
+    origin_mode: bool,
+}
+
 pub struct Emu {
     pub cols: usize,
     pub rows: usize,
-    pub grid: Vec<Cell>,
+    pub grid: Grid,
+    /// Rows scrolled off the top of the screen, oldest first.
+    pub scrollback: History,
+    pub scrollback_limit: usize,
+    /// How many rows the view is scrolled back from the live screen.
+    pub display_offset: usize,
     pub cur_x: usize,
     pub cur_y: usize,
+    /// Shape/blink as requested by the application (DECSCUSR).
+    pub cursor_style: CursorStyle,
+    /// DECTCEM: whether the cursor should be drawn at all.
+    pub cursor_visible: bool,
+    /// One entry per screen row.
+    pub line_attrs: Vec<LineAttr>,
+    /// When each screen row first received output, in milliseconds since
+    /// the Unix epoch; 0 for a row nothing has been printed on yet.
+    pub line_times: Vec<u64>,
+    /// DECSTBM scroll region, inclusive.
+    pub scroll_top: usize,
+    pub scroll_bottom: usize,
+    /// DECOM: cursor addressing is relative to the scroll region.
+    pub origin_mode: bool,
+    /// DECAWM
+    pub autowrap: bool,
+    /// IRM
+    pub insert_mode: bool,
+    /// Mode 2026: the application is mid-frame; renderers should hold the
+    /// previous frame until it is reset.
+    pub synchronized: bool,
+    /// DECSACE 2: DECCARA/DECRARA act on a rectangle instead of a stream.
+    pub rect_extent: bool,
+    /// Let applications move/resize/iconify the window via XTWINOPS.
+    /// Size reports are always answered; manipulation is opt-in.
+    pub allow_window_ops: bool,
+    /// Answer CSI 20/21 t with the title. Off by default: the reply lands
+    /// in the shell's input, and a title is whatever the output last set.
+    pub allow_title_reports: bool,
+    /// Answer OSC 52 clipboard reads.
+    pub allow_clipboard_read: bool,
+    /// Caps on what output may cost.
+    pub limits: Limits,
+    /// How many columns each character takes.
+    pub width_policy: WidthPolicy,
+    /// Cell size in pixels, used for XTWINOPS size reports.
+    cell_px: (u32, u32),
+    /// Last title set through OSC 0/2.
+    pub title: String,
+    /// Last working directory reported through OSC 7.
+    pub cwd: Option<String>,
+    /// Last OSC 9;4 progress report.
+    pub progress: Progress,
+    pub charsets: Charsets,
+    /// Sequences the emulator did not act on.
+    pub unknown: Unknown,
+    default_cursor_style: CursorStyle,
+    saved: Option<SavedCursor>,
+    bell: bool,
+    listener: Box<dyn EventListener>,
     parser: Parser,
-    cur_fg: Rgba,
-    cur_bg: Rgba,
+    /// The parser is known to be in its ground state, so printable ASCII
+    /// can bypass it. Conservative: false until a print or a CSI/ESC
+    /// dispatch proves otherwise. OSC and DCS ends do not count, since an
+    /// ST terminator's `\` is still pending when they fire.
+    ground: bool,
+    /// Current SGR state, and its id for newly printed cells.
+    pen: Style,
+    pen_id: StyleId,
+    styles: Styles,
+    /// Fixed time to stamp rows with instead of the wall clock.
+    clock: Option<u64>,
+    /// Recent sequences, while the inspector wants them.
+    trace: Option<Trace>,
+    /// Set by a handler that ignored the sequence being dispatched.
+    unhandled: bool,
+    /// Length of the OSC or DCS string being received.
+    strings: Strings,
+    /// Start (ms) of the current one-second title window and the titles
+    /// accepted in it.
+    title_window: (u64, u32),
+    /// Newest title held back by the rate limit, applied by [`Emu::tick`]
+    /// once the window closes.
+    pending_title: Option<String>,
 }
 
 impl Emu {
@@ -42,36 +306,319 @@ impl Emu {
         Self {
             cols,
             rows,
-            grid: vec![Cell::default(); cols * rows],
+            grid: Grid::new(cols, rows),
+            scrollback: History::new(cols),
+            scrollback_limit: 10_000,
+            display_offset: 0,
             cur_x: 0,
             cur_y: 0,
+            cursor_style: CursorStyle::default(),
+            cursor_visible: true,
+            line_attrs: vec![LineAttr::Normal; rows],
+            line_times: vec![0; rows],
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            origin_mode: false,
+            autowrap: true,
+            insert_mode: false,
+            synchronized: false,
+            rect_extent: false,
+            allow_window_ops: false,
+            allow_title_reports: false,
+            allow_clipboard_read: false,
+            limits: Limits::default(),
+            width_policy: WidthPolicy::default(),
+            cell_px: (0, 0),
+            title: String::new(),
+            cwd: None,
+            progress: Progress::default(),
+            charsets: Charsets::default(),
+            unknown: Unknown::default(),
+            default_cursor_style: CursorStyle::default(),
+            saved: None,
+            bell: false,
+            listener: Box::new(NoopListener),
             parser: Parser::new(),
-            cur_fg: Cell::default().fg,
-            cur_bg: Cell::default().bg,
+            ground: true,
+            pen: Style::default(),
+            pen_id: StyleId::DEFAULT,
+            styles: Styles::new(),
+            clock: None,
+            trace: None,
+            unhandled: false,
+            strings: Strings::default(),
+            title_window: (0, 0),
+            pending_title: None,
         }
     }
 
     pub fn resize(&mut self, cols: usize, rows: usize) {
+        if (max(1, cols), max(1, rows)) == (self.cols, self.rows) {
+            return;
+        }
         self.cols = max(1, cols);
         self.rows = max(1, rows);
-        self.grid = vec![Cell::default(); self.cols * self.rows];
+        self.grid = Grid::new(self.cols, self.rows);
+        self.scrollback.set_cols(self.cols);
+        while self.scrollback.len() > self.scrollback_capacity() {
+            self.scrollback.pop_front();
+        }
+        self.display_offset = 0;
+        self.line_attrs = vec![LineAttr::Normal; self.rows];
+        self.line_times = vec![0; self.rows];
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
         self.cur_x = 0;
         self.cur_y = 0;
     }
 
-    #[inline]
-    fn idx(&self, x: usize, y: usize) -> usize {
-        y * self.cols + x
+    pub fn cell(&self, x: usize, y: usize) -> &Cell {
+        &self.grid[y][x]
+    }
+
+    pub fn style(&self, id: StyleId) -> &Style {
+        self.styles.get(id)
+    }
+
+    /// Current SGR state, applied to newly printed cells.
+    pub fn pen(&self) -> &Style {
+        &self.pen
+    }
+
+    pub fn cell_style(&self, x: usize, y: usize) -> &Style {
+        self.style(self.cell(x, y).style)
+    }
+
+    /// Row `y` of the viewport, taking `display_offset` into account.
+    /// Scrollback rows spilled to disk are read back as owned copies.
+    pub fn visible_row(&self, y: usize) -> Cow<'_, [Cell]> {
+        let off = min(self.display_offset, self.scrollback.len());
+        if y < off {
+            let i = self.scrollback.len() - off + y;
+            self.scrollback.get(i).expect("row within scrollback")
+        } else {
+            Cow::Borrowed(&self.grid[y - off])
+        }
+    }
+
+    /// Line `line` of the whole history: scrollback rows first, oldest at
+    /// 0, then the screen.
+    pub fn history_line(&self, line: usize) -> Cow<'_, [Cell]> {
+        let sb = self.scrollback.len();
+        if line < sb {
+            self.scrollback.get(line).expect("row within scrollback")
+        } else {
+            Cow::Borrowed(&self.grid[line - sb])
+        }
+    }
+
+    /// Arrival time of viewport row `y` (see [`Emu::line_times`]).
+    pub fn visible_line_time(&self, y: usize) -> u64 {
+        let off = min(self.display_offset, self.scrollback.len());
+        if y < off {
+            self.scrollback.time(self.scrollback.len() - off + y)
+        } else {
+            self.line_times[y - off]
+        }
+    }
+
+    /// Arrival time of `line`, numbered as in [`Emu::history_line`].
+    pub fn history_line_time(&self, line: usize) -> u64 {
+        let sb = self.scrollback.len();
+        if line < sb {
+            self.scrollback.time(line)
+        } else {
+            self.line_times.get(line - sb).copied().unwrap_or(0)
+        }
+    }
+
+    /// Stamp new rows with `now` (milliseconds since the Unix epoch) rather
+    /// than the wall clock, e.g. while replaying a recording; `None` goes
+    /// back to the wall clock.
+    pub fn set_clock(&mut self, now: Option<u64>) {
+        self.clock = now;
+    }
+
+    /// Whether the parser is between sequences, so a snapshot taken now
+    /// loses nothing that is still in flight.
+    pub(crate) fn is_idle(&self) -> bool {
+        self.ground
+    }
+
+    /// Apply a title change held back by the rate limit once its window
+    /// has closed. Call it regularly, e.g. once per frame.
+    pub fn tick(&mut self) {
+        if self.pending_title.is_none() || !self.take_title_slot() {
+            return;
+        }
+        if let Some(title) = self.pending_title.take() {
+            self.apply_title(title);
+        }
+    }
+
+    /// Spill scrollback beyond a few thousand rows to compressed pages in
+    /// `dir`, which is deleted again with the emulator. Clears the current
+    /// scrollback.
+    pub fn spill_scrollback_to(&mut self, dir: PathBuf) -> anyhow::Result<()> {
+        self.display_offset = 0;
+        self.scrollback.spill_to(dir)
+    }
+
+    /// Delete the spilled scrollback pages now rather than when the emulator
+    /// is dropped; the rows they held are lost.
+    pub fn discard_spilled_scrollback(&mut self) {
+        self.display_offset = 0;
+        self.scrollback.drop_spill();
+    }
+
+    /// Line attribute of viewport row `y`; scrollback rows are always normal.
+    pub fn visible_line_attr(&self, y: usize) -> LineAttr {
+        let off = min(self.display_offset, self.scrollback.len());
+        if y < off {
+            LineAttr::Normal
+        } else {
+            self.line_attrs[y - off]
+        }
+    }
+
+    /// Move the viewport into (positive) or out of (negative) the scrollback.
+    pub fn scroll_display(&mut self, delta: isize) {
+        let off = self.display_offset as isize + delta;
+        self.display_offset = off.clamp(0, self.scrollback.len() as isize) as usize;
+    }
+
+    /// Append `row`, handing back a buffer the caller can reuse: the evicted
+    /// oldest row once the scrollback is full.
+    fn push_scrollback(&mut self, row: Vec<Cell>, time: u64) -> Option<Vec<Cell>> {
+        let capacity = self.scrollback_capacity();
+        if capacity == 0 {
+            return Some(row);
+        }
+        let mut evicted = None;
+        while self.scrollback.len() >= capacity {
+            evicted = self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(row, time);
+        if self.display_offset > 0 {
+            // Keep the viewport anchored on the same text for as long as the
+            // scrollback still holds it.
+            self.display_offset = min(self.display_offset + 1, self.scrollback.len());
+        }
+        evicted
+    }
+
+    /// Rows the scrollback may hold: `scrollback_limit`, or fewer when rows
+    /// this wide would take more than `limits.scrollback_mib` of memory.
+    pub fn scrollback_capacity(&self) -> usize {
+        let row = if self.scrollback.is_spilling() {
+            mem::size_of::<u64>()
+        } else {
+            self.cols * mem::size_of::<Cell>() + mem::size_of::<u64>()
+        };
+        min(
+            self.scrollback_limit,
+            self.limits.scrollback_mib.saturating_mul(1 << 20) / row,
+        )
+    }
+
+    /// Usable columns on row `y`; double-width rows hold half as many.
+    pub fn line_cols(&self, y: usize) -> usize {
+        if self.line_attrs[y].is_double() {
+            max(1, self.cols / 2)
+        } else {
+            self.cols
+        }
+    }
+
+    /// Panic if the internal bookkeeping is inconsistent. Cheap enough to
+    /// call after every `on_bytes` from fuzzers and stress tests.
+    pub fn check_invariants(&self) {
+        assert_eq!(self.grid.len(), self.rows, "grid rows");
+        assert_eq!(self.line_attrs.len(), self.rows, "line attrs");
+        assert_eq!(self.line_times.len(), self.rows, "line times");
+        // `cur_x == cols` is the pending-wrap position after the last column.
+        assert!(
+            self.cur_x <= self.cols,
+            "cursor x {} > {}",
+            self.cur_x,
+            self.cols
+        );
+        assert!(
+            self.cur_y < self.rows,
+            "cursor y {} >= {}",
+            self.cur_y,
+            self.rows
+        );
+        assert!(
+            self.scroll_top <= self.scroll_bottom && self.scroll_bottom < self.rows,
+            "scroll region {}..{} with {} rows",
+            self.scroll_top,
+            self.scroll_bottom,
+            self.rows
+        );
+        assert!(
+            self.scrollback.len() <= self.scrollback_capacity(),
+            "scrollback limit"
+        );
+        assert!(
+            self.display_offset <= self.scrollback.len(),
+            "display offset"
+        );
+        self.scrollback.check_invariants();
+        for row in self.grid.iter() {
+            assert_eq!(row.len(), self.cols, "grid row width");
+        }
+        let styles = self.styles.len();
+        let live = self.grid.iter().chain(self.scrollback.hot_rows());
+        for cell in live.flatten() {
+            assert!((cell.style.0 as usize) < styles, "dangling style id");
+        }
+        assert_eq!(self.styles.get(self.pen_id), &self.pen, "pen style");
+    }
+
+    /// Style used when the application resets the cursor with `CSI 0 SP q`.
+    pub fn set_default_cursor_style(&mut self, style: CursorStyle) {
+        if self.cursor_style == self.default_cursor_style {
+            self.cursor_style = style;
+        }
+        self.default_cursor_style = style;
+    }
+
+    /// Pixel size of one cell as laid out by the app (for `CSI 14/16 t`).
+    pub fn set_cell_size(&mut self, width: u32, height: u32) {
+        self.cell_px = (width, height);
+    }
+
+    /// Route title changes, bells, replies etc. to `listener`.
+    pub fn set_listener(&mut self, listener: Box<dyn EventListener>) {
+        self.listener = listener;
+    }
+
+    fn emit(&mut self, event: EmuEvent) {
+        self.listener.on_event(event);
+    }
+
+    fn reply(&mut self, bytes: impl Into<Vec<u8>>) {
+        self.emit(EmuEvent::PtyWrite(bytes.into()));
+    }
+
+    /// Intern `style`, first compacting the table if it has grown large.
+    /// Cell style ids must not be held across this call.
+    fn intern(&mut self, style: Style) -> StyleId {
+        if self.styles.needs_compaction() {
+            self.compact_styles();
+        }
+        self.styles.intern(style)
+    }
+
+    /// Re-intern the pen after changing it.
+    fn update_pen(&mut self) {
+        self.pen_id = self.intern(self.pen);
     }
 
     pub fn draw_char(&mut self, c: char) {
         if c == '\n' {
-            self.cur_x = 0;
-            if self.cur_y + 1 >= self.rows {
-                self.scroll_up();
-            } else {
-                self.cur_y += 1;
-            }
+            self.linefeed();
             return;
         }
         if c == '\r' {
@@ -80,95 +627,929 @@ impl Emu {
         }
         if c == '\x08' {
             // BS
-            if self.cur_x > 0 {
-                self.cur_x -= 1;
-            }
+            self.cur_x = min(self.cur_x, self.cols - 1).saturating_sub(1);
             return;
         }
         if c.is_control() {
             return;
         }
-        let w = UnicodeWidthChar::width(c).unwrap_or(1).max(1);
-        if self.cur_x + w > self.cols {
-            self.cur_x = 0;
-            if self.cur_y + 1 >= self.rows {
-                self.scroll_up();
+        if c == VS15 || c == VS16 {
+            self.variation_selector(c);
+            return;
+        }
+        // A wide char on a one-column line is squeezed into that column.
+        let mut w = self.char_width(c);
+        let line_cols = self.line_cols(self.cur_y);
+        w = min(w, line_cols);
+        if self.cur_x + w > line_cols {
+            if self.autowrap {
+                self.cur_x = 0;
+                self.linefeed();
             } else {
-                self.cur_y += 1;
+                self.cur_x = line_cols.saturating_sub(w);
             }
         }
-        let i = self.idx(self.cur_x, self.cur_y);
-        self.grid[i] = Cell {
+        let line_cols = self.line_cols(self.cur_y);
+        w = min(w, line_cols);
+        let (x, row) = (self.cur_x, &mut self.grid[self.cur_y]);
+        if self.insert_mode && x + w < line_cols {
+            row.copy_within(x..line_cols - w, x + w);
+        }
+        row[x] = Cell {
             ch: c,
-            fg: self.cur_fg,
-            bg: self.cur_bg,
+            style: self.pen_id,
         };
         self.cur_x += w;
+        self.stamp_line();
+    }
+
+    /// Columns `c` takes under the current [`WidthPolicy`].
+    pub fn char_width(&self, c: char) -> usize {
+        self.width_policy.of(c)
+    }
+
+    /// VS16 after a narrow character widens it to two columns in emoji
+    /// presentation: the selector is kept in the cell that the wider glyph
+    /// covers. Anywhere else, or when the policy says so, it is dropped.
+    fn variation_selector(&mut self, c: char) {
+        let (x, y) = (self.cur_x, self.cur_y);
+        if c != VS16 || !self.width_policy.emoji_vs16 || x == 0 || x >= self.line_cols(y) {
+            return;
+        }
+        let row = &self.grid[y];
+        let prev = row[x - 1].ch;
+        let after_wide = x >= 2 && self.char_width(row[x - 2].ch) == 2;
+        if prev.is_ascii() || self.char_width(prev) != 1 || after_wide {
+            return;
+        }
+        self.grid[y][x] = Cell {
+            ch: c,
+            style: self.pen_id,
+        };
+        self.cur_x += 1;
+    }
+
+    /// Record when the cursor row first got output; one clock read per line.
+    fn stamp_line(&mut self) {
+        if self.line_times[self.cur_y] == 0 {
+            self.line_times[self.cur_y] = self.clock.unwrap_or_else(now_ms);
+        }
+    }
+
+    /// Bulk `draw_char` for printable ASCII: every char is one column wide,
+    /// so whole stretches up to the margin are written at once.
+    fn print_ascii(&mut self, run: &[u8]) {
+        if self.insert_mode {
+            run.iter().for_each(|&b| self.draw_char(b as char));
+            return;
+        }
+        let mut rest = run;
+        while !rest.is_empty() {
+            let line_cols = self.line_cols(self.cur_y);
+            if self.cur_x >= line_cols {
+                if self.autowrap {
+                    self.cur_x = 0;
+                    self.linefeed();
+                } else {
+                    // Everything past the margin lands on the last column.
+                    self.cur_x = line_cols - 1;
+                    rest = &rest[rest.len() - 1..];
+                }
+                continue;
+            }
+            let (x, n) = (self.cur_x, min(rest.len(), line_cols - self.cur_x));
+            let style = self.pen_id;
+            for (cell, &b) in self.grid[self.cur_y][x..x + n].iter_mut().zip(rest) {
+                *cell = Cell {
+                    ch: b as char,
+                    style,
+                };
+            }
+            self.cur_x += n;
+            rest = &rest[n..];
+            self.stamp_line();
+        }
+    }
+
+    /// LF / IND: move down, scrolling the region at its bottom margin.
+    fn linefeed(&mut self) {
+        if self.cur_y == self.scroll_bottom {
+            self.scroll_up();
+        } else if self.cur_y + 1 < self.rows {
+            self.cur_y += 1;
+        }
+    }
+
+    /// RI: move up, scrolling the region down at its top margin.
+    fn reverse_index(&mut self) {
+        if self.cur_y == self.scroll_top {
+            self.scroll_down();
+        } else if self.cur_y > 0 {
+            self.cur_y -= 1;
+        }
+    }
+
+    /// Absolute cursor move; `y` is relative to the region in origin mode.
+    fn goto(&mut self, x: usize, y: usize) {
+        let (top, bottom) = if self.origin_mode {
+            (self.scroll_top, self.scroll_bottom)
+        } else {
+            (0, self.rows - 1)
+        };
+        self.cur_y = min(top + y, bottom);
+        self.cur_x = min(x, self.line_cols(self.cur_y) - 1);
+    }
+
+    /// Relative vertical move that stops at the margins when starting inside them.
+    fn move_y(&mut self, dy: isize) {
+        let (top, bottom) = if (self.scroll_top..=self.scroll_bottom).contains(&self.cur_y) {
+            (self.scroll_top, self.scroll_bottom)
+        } else {
+            (0, self.rows - 1)
+        };
+        let y = self.cur_y as isize + dy;
+        self.cur_y = y.clamp(top as isize, bottom as isize) as usize;
+        self.cur_x = min(self.cur_x, self.line_cols(self.cur_y) - 1);
+    }
+
+    fn move_x(&mut self, dx: isize) {
+        let x = min(self.cur_x, self.cols - 1) as isize + dx;
+        self.cur_x = x.clamp(0, self.line_cols(self.cur_y) as isize - 1) as usize;
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved = Some(SavedCursor {
+            x: self.cur_x,
+            y: self.cur_y,
+            pen: self.pen,
+            origin_mode: self.origin_mode,
+        });
+    }
+
+    fn restore_cursor(&mut self) {
+        let saved = self.saved.unwrap_or(SavedCursor {
+            x: 0,
+            y: 0,
+            pen: Style::default(),
+            origin_mode: false,
+        });
+        self.cur_x = min(saved.x, self.cols);
+        self.cur_y = min(saved.y, self.rows - 1);
+        self.pen = saved.pen;
+        self.update_pen();
+        self.origin_mode = saved.origin_mode;
+    }
+
+    /// DECSTBM: `CSI top ; bottom r`.
+    fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = min(bottom, self.rows - 1);
+        if top >= bottom {
+            return;
+        }
+        self.scroll_top = top;
+        self.scroll_bottom = bottom;
+        self.goto(0, 0);
+    }
+
+    /// DECSTR: reset modes and pen without touching the screen contents.
+    fn soft_reset(&mut self) {
+        self.cursor_visible = true;
+        self.synchronized = false;
+        self.insert_mode = false;
+        self.origin_mode = false;
+        self.autowrap = true;
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+        self.pen = Style::default();
+        self.pen_id = StyleId::DEFAULT;
+        self.saved = None;
+    }
+
+    /// RIS: back to the power-on state. The title and listener are kept.
+    fn full_reset(&mut self) {
+        self.soft_reset();
+        self.cursor_style = self.default_cursor_style;
+        self.rect_extent = false;
+        self.progress = Progress::default();
+        self.charsets = Charsets::default();
+        self.line_attrs.fill(LineAttr::Normal);
+        self.clear_all();
+    }
+
+    /// DECALN: fill the screen with `E` for alignment checks.
+    fn screen_alignment(&mut self) {
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+        self.line_attrs.fill(LineAttr::Normal);
+        self.line_times.fill(0);
+        self.grid.fill(Cell {
+            ch: 'E',
+            ..Cell::BLANK
+        });
+        self.cur_x = 0;
+        self.cur_y = 0;
+    }
+
+    /// DECDWL/DECDHL/DECSWL apply to the whole cursor row.
+    fn set_line_attr(&mut self, attr: LineAttr) {
+        self.line_attrs[self.cur_y] = attr;
+        if attr.is_double() {
+            let half = max(1, self.cols / 2);
+            self.grid[self.cur_y][half..].fill(Cell::BLANK);
+            self.cur_x = min(self.cur_x, half - 1);
+        }
     }
 
     fn clear_all(&mut self) {
-        self.grid.fill(Cell::default());
+        self.grid.fill(Cell::BLANK);
+        self.line_times.fill(0);
         self.cur_x = 0;
         self.cur_y = 0;
     }
 
-    fn scroll_up(&mut self) {
-        if self.rows <= 1 {
+    /// Blank cell carrying the current background (xterm's BCE behaviour).
+    fn blank(&mut self) -> Cell {
+        let style = Style {
+            bg: self.pen.bg,
+            ..Style::default()
+        };
+        Cell {
+            ch: ' ',
+            style: self.intern(style),
+        }
+    }
+
+    /// ED: `CSI Ps J`.
+    fn erase_display(&mut self, mode: u16) {
+        let blank = self.blank();
+        let (x, y) = (min(self.cur_x, self.cols - 1), self.cur_y);
+        match mode {
+            0 => {
+                self.grid[y][x..].fill(blank);
+                (y + 1..self.rows).for_each(|y| self.grid[y].fill(blank));
+                self.line_times[y + 1..].fill(0);
+            }
+            1 => {
+                (0..y).for_each(|y| self.grid[y].fill(blank));
+                self.grid[y][..=x].fill(blank);
+                self.line_times[..y].fill(0);
+            }
+            2 => {
+                self.grid.fill(blank);
+                self.line_times.fill(0);
+            }
+            3 => {
+                self.scrollback.clear();
+                self.display_offset = 0;
+                return;
+            }
+            _ => return,
+        }
+        let first = if mode == 0 { self.cur_y } else { 0 };
+        let last = if mode == 1 { self.cur_y } else { self.rows - 1 };
+        self.line_attrs[first..=last].fill(LineAttr::Normal);
+    }
+
+    /// EL: `CSI Ps K`.
+    fn erase_line(&mut self, mode: u16) {
+        let blank = self.blank();
+        let x = min(self.cur_x, self.cols - 1);
+        let range = match mode {
+            0 => x..self.cols,
+            1 => 0..x + 1,
+            2 => 0..self.cols,
+            _ => return,
+        };
+        self.grid[self.cur_y][range].fill(blank);
+    }
+
+    /// ECH: `CSI n X`.
+    fn erase_chars(&mut self, n: usize) {
+        let blank = self.blank();
+        let x = min(self.cur_x, self.cols - 1);
+        let end = min(x + n, self.cols);
+        self.grid[self.cur_y][x..end].fill(blank);
+    }
+
+    /// SGR: `CSI ... m`.
+    fn sgr(&mut self, params: &Params) {
+        let mut iter = params.iter();
+        if params.is_empty() {
+            self.pen = Style::default();
+            self.pen_id = StyleId::DEFAULT;
             return;
         }
-        let w = self.cols;
-        let len = self.grid.len();
-        self.grid.copy_within(w.., 0);
-        for c in self.grid[(len - w)..].iter_mut() {
-            *c = Cell::default();
+        while let Some(p) = iter.next() {
+            let pen = &mut self.pen;
+            match p[0] {
+                0 => *pen = Style::default(),
+                1 => pen.flags.set(Flags::BOLD, true),
+                2 => pen.flags.set(Flags::DIM, true),
+                3 => pen.flags.set(Flags::ITALIC, true),
+                4 => {
+                    pen.underline = match p.get(1) {
+                        None | Some(1) => Underline::Single,
+                        Some(0) => Underline::None,
+                        Some(2) => Underline::Double,
+                        Some(3) => Underline::Curly,
+                        Some(4) => Underline::Dotted,
+                        Some(5) => Underline::Dashed,
+                        Some(_) => pen.underline,
+                    }
+                }
+                5 | 6 => pen.flags.set(Flags::BLINK, true),
+                7 => pen.flags.set(Flags::INVERSE, true),
+                8 => pen.flags.set(Flags::HIDDEN, true),
+                9 => pen.flags.set(Flags::STRIKE, true),
+                21 => pen.underline = Underline::Double,
+                22 => {
+                    pen.flags.set(Flags::BOLD, false);
+                    pen.flags.set(Flags::DIM, false);
+                }
+                23 => pen.flags.set(Flags::ITALIC, false),
+                24 => pen.underline = Underline::None,
+                25 => pen.flags.set(Flags::BLINK, false),
+                27 => pen.flags.set(Flags::INVERSE, false),
+                28 => pen.flags.set(Flags::HIDDEN, false),
+                29 => pen.flags.set(Flags::STRIKE, false),
+                n @ 30..=37 => pen.fg = Color::Indexed((n - 30) as u8),
+                38 => {
+                    if let Some(c) = extended_color(p, &mut iter) {
+                        self.pen.fg = c;
+                    }
+                }
+                39 => pen.fg = Color::Default,
+                n @ 40..=47 => pen.bg = Color::Indexed((n - 40) as u8),
+                48 => {
+                    if let Some(c) = extended_color(p, &mut iter) {
+                        self.pen.bg = c;
+                    }
+                }
+                49 => pen.bg = Color::Default,
+                58 => {
+                    if let Some(c) = extended_color(p, &mut iter) {
+                        self.pen.ul = c;
+                    }
+                }
+                59 => pen.ul = Color::Default,
+                n @ 90..=97 => pen.fg = Color::Indexed((n - 90 + 8) as u8),
+                n @ 100..=107 => pen.bg = Color::Indexed((n - 100 + 8) as u8),
+                _ => self.unhandled = true,
+            }
+        }
+        self.update_pen();
+    }
+
+    /// DECSCUSR: `CSI Ps SP q`.
+    fn set_cursor_style(&mut self, ps: u16) {
+        let (shape, blinking) = match ps {
+            0 => {
+                self.cursor_style = self.default_cursor_style;
+                return;
+            }
+            1 => (CursorShape::Block, true),
+            2 => (CursorShape::Block, false),
+            3 => (CursorShape::Underline, true),
+            4 => (CursorShape::Underline, false),
+            5 => (CursorShape::Bar, true),
+            6 => (CursorShape::Bar, false),
+            _ => {
+                self.unhandled = true;
+                return;
+            }
+        };
+        self.cursor_style = CursorStyle { shape, blinking };
+    }
+
+    /// DECSET/DECRST for the private modes we understand.
+    fn set_private_mode(&mut self, mode: u16, on: bool) {
+        match mode {
+            6 => {
+                self.origin_mode = on;
+                self.goto(0, 0);
+            }
+            7 => self.autowrap = on,
+            12 => self.cursor_style.blinking = on,
+            25 => self.cursor_visible = on,
+            2026 => self.synchronized = on,
+            _ => self.unhandled = true,
         }
     }
 
+    fn private_mode(&self, mode: u16) -> Option<bool> {
+        match mode {
+            6 => Some(self.origin_mode),
+            7 => Some(self.autowrap),
+            12 => Some(self.cursor_style.blinking),
+            25 => Some(self.cursor_visible),
+            2026 => Some(self.synchronized),
+            _ => None,
+        }
+    }
+
+    /// DECRQM for private modes: `CSI ? Ps $ p` -> `CSI ? Ps ; Pm $ y`.
+    fn report_private_mode(&mut self, mode: u16) {
+        let pm = match self.private_mode(mode) {
+            Some(true) => 1,
+            Some(false) => 2,
+            None => 0,
+        };
+        self.reply(format!("\x1b[?{mode};{pm}$y"));
+    }
+
+    /// Scroll the region up by one row. The row leaving the top of the
+    /// screen moves into the scrollback as is and the row evicted from the
+    /// scrollback, if any, comes back as the new bottom line, so a full
+    /// screen scroll copies no cells and allocates nothing once warm.
+    fn scroll_up(&mut self) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        self.grid.rotate_up(top, bottom);
+        if top == 0 {
+            let old = mem::take(self.grid.row_mut(bottom));
+            let spare = self
+                .push_scrollback(old, self.line_times[0])
+                .unwrap_or_default();
+            let row = self.grid.row_mut(bottom);
+            *row = spare;
+            row.clear();
+            row.resize(self.cols, Cell::BLANK);
+        } else {
+            self.grid[bottom].fill(Cell::BLANK);
+        }
+        self.line_attrs.copy_within(top + 1..=bottom, top);
+        self.line_attrs[bottom] = LineAttr::Normal;
+        self.line_times.copy_within(top + 1..=bottom, top);
+        self.line_times[bottom] = 0;
+    }
+
+    /// Scroll the region down by one row.
+    fn scroll_down(&mut self) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        self.grid.rotate_down(top, bottom);
+        self.grid[top].fill(Cell::BLANK);
+        self.line_attrs.copy_within(top..bottom, top + 1);
+        self.line_attrs[top] = LineAttr::Normal;
+        self.line_times.copy_within(top..bottom, top + 1);
+        self.line_times[top] = 0;
+    }
+
     /// Feed raw PTY bytes: use vte to parse ANSI and print UTF-8 safely.
+    /// Runs of printable ASCII seen while the parser is idle skip it and
+    /// are written into the row in bulk.
     pub fn on_bytes(&mut self, bytes: &[u8]) {
         let mut parser = std::mem::take(&mut self.parser);
-        for &b in bytes {
+        let mut i = 0;
+        while i < bytes.len() {
+            if self.ground {
+                let run = bytes[i..]
+                    .iter()
+                    .take_while(|b| (0x20..0x7f).contains(*b))
+                    .count();
+                if run > 0 {
+                    self.print_ascii(&bytes[i..i + run]);
+                    i += run;
+                    continue;
+                }
+            }
+            // C0 controls other than ESC execute without leaving ground;
+            // anything else may start a sequence.
+            let b = bytes[i];
+            i += 1;
+            match self.strings.step(b, &self.limits) {
+                Step::Feed => {}
+                Step::Skip => continue,
+                Step::Overflow { kind, max } => {
+                    // Drop the buffered string; the rest is swallowed up to
+                    // its terminator. A DCS was already counted by `hook`.
+                    parser = Parser::new();
+                    self.ground = false;
+                    self.unhandled = kind == SeqKind::Osc;
+                    let name = if kind == SeqKind::Osc { "OSC" } else { "DCS" };
+                    self.note(kind, || format!("{name} longer than {max} bytes dropped"));
+                    continue;
+                }
+            }
+            self.ground &= b != 0x1b && (b < 0x20 || b == 0x7f);
             parser.advance(self, b);
         }
         self.parser = parser;
+        // Coalesce a burst of BELs into one event per chunk.
+        if std::mem::take(&mut self.bell) {
+            self.emit(EmuEvent::Bell);
+        }
+    }
+
+    /// DSR / DA queries that expect an answer on the PTY.
+    fn report(&mut self, ints: &[u8], action: char, ps: u16) {
+        match (ints, action, ps) {
+            ([], 'n', 5) => self.reply(*b"\x1b[0n"),
+            ([], 'n', 6) => {
+                let x = min(self.cur_x, self.cols - 1) + 1;
+                let y = self.cur_y + 1;
+                self.reply(format!("\x1b[{y};{x}R"));
+            }
+            // VT420 with selective erase, ANSI color and rectangular editing.
+            ([], 'c', 0) => self.reply(*b"\x1b[?64;6;22;28c"),
+            ([b'>'], 'c', 0) => self.reply(*b"\x1b[>0;10;1c"),
+            _ => self.unhandled = true,
+        }
+    }
+
+    /// XTWINOPS: `CSI Ps ; Ps ; Ps t`.
+    fn window_op(&mut self, params: &Params) {
+        let mut it = params.iter().map(|p| p[0]);
+        let ps = it.next().unwrap_or(0);
+        let a = it.next();
+        let b = it.next();
+        let (cw, ch) = self.cell_px;
+        let op = match ps {
+            11 => return self.reply(*b"\x1b[1t"),
+            14 => {
+                let w = cw.saturating_mul(self.cols as u32);
+                let h = ch.saturating_mul(self.rows as u32);
+                return self.reply(format!("\x1b[4;{h};{w}t"));
+            }
+            16 => return self.reply(format!("\x1b[6;{ch};{cw}t")),
+            18 => return self.reply(format!("\x1b[8;{};{}t", self.rows, self.cols)),
+            19 => return self.reply(format!("\x1b[9;{};{}t", self.rows, self.cols)),
+            // Icon label and title; we keep one string for both.
+            20 | 21 => {
+                if !self.allow_title_reports {
+                    log::debug!("XTWINOPS {ps} title report refused by policy");
+                    return;
+                }
+                let kind = if ps == 20 { 'L' } else { 'l' };
+                return self.reply(format!("\x1b]{kind}{}\x1b\\", self.title));
+            }
+            1 => WindowOp::Deiconify,
+            2 => WindowOp::Iconify,
+            3 => WindowOp::Move {
+                x: a.unwrap_or(0) as i32,
+                y: b.unwrap_or(0) as i32,
+            },
+            4 => WindowOp::ResizePixels {
+                height: a.filter(|&v| v > 0).map(u32::from),
+                width: b.filter(|&v| v > 0).map(u32::from),
+            },
+            5 => WindowOp::Raise,
+            6 => WindowOp::Lower,
+            8 => WindowOp::ResizeCells {
+                rows: a.filter(|&v| v > 0),
+                cols: b.filter(|&v| v > 0),
+            },
+            9 => WindowOp::Maximize(a.unwrap_or(0) != 0),
+            10 => WindowOp::Fullscreen(match a.unwrap_or(0) {
+                0 => Some(false),
+                1 => Some(true),
+                _ => None,
+            }),
+            _ => {
+                self.unhandled = true;
+                return;
+            }
+        };
+        if self.allow_window_ops {
+            self.emit(EmuEvent::WindowOp(op));
+        } else {
+            log::debug!("XTWINOPS {ps} refused by policy");
+        }
+    }
+
+    /// OSC 0/2. Control characters are stripped, the title is cut to
+    /// `limits.title_chars`, and changes beyond `limits.titles_per_sec`
+    /// are held back: only the newest is kept, for [`Emu::tick`] to apply
+    /// when the window closes.
+    fn set_title(&mut self, params: &[&[u8]]) {
+        let title = params[1..].join(&b';');
+        let title: String = String::from_utf8_lossy(&title)
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(self.limits.title_chars)
+            .collect();
+        if !self.take_title_slot() {
+            log::debug!(
+                "title change deferred: over {}/s",
+                self.limits.titles_per_sec
+            );
+            self.pending_title = Some(title);
+            return;
+        }
+        self.pending_title = None;
+        self.apply_title(title);
+    }
+
+    /// Count a title change against the current one-second window; false
+    /// when the window is full.
+    fn take_title_slot(&mut self) -> bool {
+        let now = self.clock.unwrap_or_else(now_ms);
+        let (start, count) = &mut self.title_window;
+        if now.saturating_sub(*start) >= 1000 {
+            (*start, *count) = (now, 0);
+        }
+        if *count >= self.limits.titles_per_sec {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    fn apply_title(&mut self, title: String) {
+        self.title = title;
+        self.emit(EmuEvent::Title(self.title.clone()));
+    }
+
+    fn osc_clipboard(&mut self, params: &[&[u8]]) {
+        use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+        let (Some(sel), Some(data)) = (params.get(1), params.get(2)) else {
+            return;
+        };
+        let clipboard = if sel.contains(&b'c') || sel.is_empty() {
+            Clipboard::Clipboard
+        } else {
+            Clipboard::Selection
+        };
+        if *data == b"?" {
+            if !self.allow_clipboard_read {
+                log::debug!("OSC 52 read refused by policy");
+                return;
+            }
+            self.emit(EmuEvent::ClipboardLoad { clipboard });
+        } else if let Ok(data) = B64.decode(data) {
+            self.emit(EmuEvent::ClipboardStore { clipboard, data });
+        }
+    }
+
+    /// OSC 9;4;st;pr (ConEmu progress). Other OSC 9 forms are ignored.
+    fn osc_progress(&mut self, params: &[&[u8]]) {
+        if params.get(1).copied() != Some(b"4") {
+            self.unhandled = true;
+            return;
+        }
+        let num = |i: usize| {
+            params
+                .get(i)
+                .and_then(|p| std::str::from_utf8(p).ok())
+                .and_then(|p| p.parse::<u32>().ok())
+        };
+        let state = match num(2).unwrap_or(0) {
+            0 => ProgressState::None,
+            1 => ProgressState::Normal,
+            2 => ProgressState::Error,
+            3 => ProgressState::Indeterminate,
+            4 => ProgressState::Paused,
+            _ => {
+                self.unhandled = true;
+                return;
+            }
+        };
+        // Error/paused without a value keep the last percentage.
+        let percent = match (state, num(3)) {
+            (ProgressState::None, _) => 0,
+            (_, Some(p)) => p.min(100) as u8,
+            (ProgressState::Error | ProgressState::Paused, None) => self.progress.percent,
+            (_, None) => 0,
+        };
+        self.progress = Progress { state, percent };
+        self.emit(EmuEvent::Progress(self.progress));
+    }
+
+    fn osc_prompt_mark(&mut self, params: &[&[u8]]) {
+        let mark = match params.get(1).and_then(|p| p.first()) {
+            Some(b'A') => PromptMark::PromptStart,
+            Some(b'B') => PromptMark::CommandStart,
+            Some(b'C') => PromptMark::OutputStart,
+            Some(b'D') => PromptMark::CommandEnd {
+                exit_code: params
+                    .get(2)
+                    .and_then(|p| std::str::from_utf8(p).ok())
+                    .and_then(|p| p.parse().ok()),
+            },
+            _ => {
+                self.unhandled = true;
+                return;
+            }
+        };
+        self.emit(EmuEvent::PromptMark(mark));
     }
 }
 
 impl Perform for Emu {
     fn print(&mut self, c: char) {
+        self.ground = true;
         self.draw_char(c);
     }
     fn execute(&mut self, byte: u8) {
         match byte {
-            b'\n' => self.draw_char('\n'),
+            b'\n' | b'\x0b' | b'\x0c' => self.draw_char('\n'),
             b'\r' => self.draw_char('\r'),
             b'\x08' => self.draw_char('\x08'),
+            b'\x07' => self.bell = true,
+            // SO/SI
+            b'\x0e' => self.charsets.gl = 1,
+            b'\x0f' => self.charsets.gl = 0,
             _ => {}
         }
     }
-    fn csi_dispatch(&mut self, params: &Params, _ints: &[u8], _ignore: bool, action: char) {
-        match action {
-            'H' | 'f' => {
-                let mut iter = params.iter();
-                let y = iter.next().and_then(|p| p.first()).copied().unwrap_or(1);
-                let x = iter.next().and_then(|p| p.first()).copied().unwrap_or(1);
-                let x = x.saturating_sub(1) as usize;
-                let y = y.saturating_sub(1) as usize;
-                self.cur_x = min(x, self.cols.saturating_sub(1));
-                self.cur_y = min(y, self.rows.saturating_sub(1));
-            }
-            'J' => {
-                if *params.iter().next().and_then(|p| p.first()).unwrap_or(&0) == 2 {
-                    self.clear_all();
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        match params.first().copied() {
+            Some(b"0") | Some(b"2") => self.set_title(params),
+            Some(b"7") => {
+                if let Some(cwd) = params.get(1).and_then(|p| parse_file_url(p)) {
+                    self.cwd = Some(cwd.clone());
+                    self.emit(EmuEvent::Cwd(cwd));
                 }
             }
-            'm' => {
-                if *params.iter().next().and_then(|p| p.first()).unwrap_or(&0) == 0 {
-                    self.cur_fg = Cell::default().fg;
-                    self.cur_bg = Cell::default().bg;
+            Some(b"9") => self.osc_progress(params),
+            Some(b"52") => self.osc_clipboard(params),
+            Some(b"133") => self.osc_prompt_mark(params),
+            _ => self.unhandled = true,
+        }
+        self.note(SeqKind::Osc, || osc_text(params));
+    }
+    fn csi_dispatch(&mut self, params: &Params, ints: &[u8], _ignore: bool, action: char) {
+        self.ground = true;
+        match (ints, action) {
+            ([b' '], 'q') => {
+                let ps = params.iter().next().and_then(|p| p.first()).copied();
+                self.set_cursor_style(ps.unwrap_or(0));
+            }
+            ([b'?'], 'h') | ([b'?'], 'l') => {
+                for p in params.iter() {
+                    self.set_private_mode(p[0], action == 'h');
                 }
             }
-            _ => {}
+            ([b'!'], 'p') => self.soft_reset(),
+            ([], 'h') | ([], 'l') => {
+                for p in params.iter() {
+                    if p[0] == 4 {
+                        self.insert_mode = action == 'h';
+                    } else {
+                        self.unhandled = true;
+                    }
+                }
+            }
+            ([], 'H') | ([], 'f') => {
+                let y = arg(params, 0, 1) as usize - 1;
+                let x = arg(params, 1, 1) as usize - 1;
+                self.goto(x, y);
+            }
+            ([], 'A') => self.move_y(-(arg(params, 0, 1) as isize)),
+            ([], 'B') | ([], 'e') => self.move_y(arg(params, 0, 1) as isize),
+            ([], 'C') | ([], 'a') => self.move_x(arg(params, 0, 1) as isize),
+            ([], 'D') => self.move_x(-(arg(params, 0, 1) as isize)),
+            ([], 'E') | ([], 'F') => {
+                let n = arg(params, 0, 1) as isize;
+                self.move_y(if action == 'E' { n } else { -n });
+                self.cur_x = 0;
+            }
+            ([], 'G') | ([], '`') => {
+                self.cur_x = min(
+                    arg(params, 0, 1) as usize - 1,
+                    self.line_cols(self.cur_y) - 1,
+                );
+            }
+            ([], 'd') => {
+                let x = min(self.cur_x, self.cols - 1);
+                let y = arg(params, 0, 1) as usize - 1;
+                self.goto(x, y);
+            }
+            ([], 'r') => {
+                let top = arg(params, 0, 1) as usize - 1;
+                let bottom = arg(params, 1, self.rows as u16) as usize - 1;
+                self.set_scroll_region(top, bottom);
+            }
+            ([], 's') => self.save_cursor(),
+            ([], 'u') => self.restore_cursor(),
+            ([], 'J') => self.erase_display(params.iter().next().map_or(0, |p| p[0])),
+            ([], 'K') => self.erase_line(params.iter().next().map_or(0, |p| p[0])),
+            ([], 'X') => self.erase_chars(arg(params, 0, 1) as usize),
+            ([b'?'], 'J') => self.selective_erase_display(params.iter().next().map_or(0, |p| p[0])),
+            ([b'?'], 'K') => self.selective_erase_line(params.iter().next().map_or(0, |p| p[0])),
+            ([b'?', b'$'], 'p') => {
+                self.report_private_mode(params.iter().next().map_or(0, |p| p[0]));
+            }
+            ([b'"'], 'q') => {
+                let on = params.iter().next().map_or(0, |p| p[0]) == 1;
+                self.pen.flags.set(Flags::PROTECTED, on);
+                self.update_pen();
+            }
+            ([b'*'], 'x') => self.rect_extent = params.iter().next().map_or(0, |p| p[0]) == 2,
+            ([b'$'], 'x') => self.fill_rect(params),
+            ([b'$'], 'z') => self.erase_rect(params),
+            ([b'$'], '{') => self.selective_erase_rect(params),
+            ([b'$'], 'v') => self.copy_rect(params),
+            ([b'$'], 'r') => self.change_rect_attrs(params, false),
+            ([b'$'], 't') => self.change_rect_attrs(params, true),
+            ([], 't') => self.window_op(params),
+            ([], 'n') | ([], 'c') | ([b'>'], 'c') => {
+                let ps = params.iter().next().and_then(|p| p.first()).copied();
+                self.report(ints, action, ps.unwrap_or(0));
+            }
+            ([], 'm') => self.sgr(params),
+            _ => self.unhandled = true,
+        }
+        self.note(SeqKind::Csi, || csi_text("CSI", params, ints, action));
+    }
+    fn esc_dispatch(&mut self, ints: &[u8], _ignore: bool, byte: u8) {
+        self.ground = true;
+        // ST closing an OSC or DCS; the string itself was already noted.
+        if (ints, byte) == ([].as_slice(), b'\\') {
+            return;
+        }
+        match (ints, byte) {
+            ([], b'7') => self.save_cursor(),
+            ([], b'8') => self.restore_cursor(),
+            ([], b'c') => self.full_reset(),
+            ([], b'D') => self.linefeed(),
+            ([], b'E') => {
+                self.cur_x = 0;
+                self.linefeed();
+            }
+            ([], b'M') => self.reverse_index(),
+            ([b'#'], b'3') => self.set_line_attr(LineAttr::DoubleHeightTop),
+            ([b'#'], b'4') => self.set_line_attr(LineAttr::DoubleHeightBottom),
+            ([b'#'], b'5') => self.set_line_attr(LineAttr::Normal),
+            ([b'#'], b'6') => self.set_line_attr(LineAttr::DoubleWidth),
+            ([b'#'], b'8') => self.screen_alignment(),
+            // SCS: only US-ASCII is drawn as such; other sets are recorded.
+            ([i @ (b'(' | b')' | b'*' | b'+')], set) => {
+                self.charsets.g[(i - b'(') as usize] = set as char;
+                self.unhandled = set != b'B';
+            }
+            _ => self.unhandled = true,
+        }
+        self.note(SeqKind::Esc, || esc_text(ints, byte));
+    }
+    /// No DCS is supported; the introducer is logged and counted.
+    fn hook(&mut self, params: &Params, ints: &[u8], _ignore: bool, action: char) {
+        self.unhandled = true;
+        self.note(SeqKind::Dcs, || csi_text("DCS", params, ints, action));
+    }
+}
+
+/// Parse the tail of SGR 38/48/58 in either the `38:5:n` / `38:2::r:g:b`
+/// sub-parameter form or the legacy `38;5;n` / `38;2;r;g;b` form.
+fn extended_color<'a>(p: &[u16], rest: &mut impl Iterator<Item = &'a [u16]>) -> Option<Color> {
+    let byte = |v: u16| v.min(255) as u8;
+    if p.len() > 1 {
+        return match p[1] {
+            5 => p.get(2).map(|&n| Color::Indexed(byte(n))),
+            2 => {
+                // An optional color-space id precedes r:g:b when all six are present.
+                let rgb = if p.len() >= 6 { &p[3..6] } else { p.get(2..5)? };
+                Some(Color::Rgb(byte(rgb[0]), byte(rgb[1]), byte(rgb[2])))
+            }
+            _ => None,
+        };
+    }
+    match rest.next()?.first()? {
+        5 => Some(Color::Indexed(byte(*rest.next()?.first()?))),
+        2 => {
+            let r = *rest.next()?.first()?;
+            let g = *rest.next()?.first()?;
+            let b = *rest.next()?.first()?;
+            Some(Color::Rgb(byte(r), byte(g), byte(b)))
+        }
+        _ => None,
+    }
+}
+
+/// Parameter `i`, with 0/missing replaced by `default` as CSI requires.
+fn arg(params: &Params, i: usize, default: u16) -> u16 {
+    match params.iter().nth(i).and_then(|p| p.first()).copied() {
+        None | Some(0) => default,
+        Some(v) => v,
+    }
+}
+
+/// Wall-clock time in milliseconds since the Unix epoch.
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// `file://host/some%20path` -> `/some path`. Plain paths are accepted as is.
+fn parse_file_url(raw: &[u8]) -> Option<String> {
+    let s = std::str::from_utf8(raw).ok()?;
+    let path = match s.strip_prefix("file://") {
+        Some(rest) => &rest[rest.find('/')?..],
+        None => s,
+    };
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
         }
+        out.push(bytes[i]);
+        i += 1;
     }
+    String::from_utf8(out).ok()
 }
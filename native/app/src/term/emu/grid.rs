@@ -0,0 +1,83 @@
+//! Screen storage as a ring of rows.
+//!
+//! Scrolling the whole screen only moves a row pointer; partial scroll
+//! regions shift at most `rows` pointers. Cells are never copied.
+
+use std::collections::VecDeque;
+use std::ops::{Index, IndexMut};
+
+use super::Cell;
+
+/// The visible screen, indexed by row: `grid[y][x]`. Every row is exactly
+/// `cols` cells wide.
+pub struct Grid {
+    rows: VecDeque<Vec<Cell>>,
+}
+
+impl Grid {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            rows: (0..rows).map(|_| vec![Cell::BLANK; cols]).collect(),
+        }
+    }
+
+    /// Number of rows.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Rows top to bottom.
+    pub fn iter(&self) -> impl Iterator<Item = &[Cell]> {
+        self.rows.iter().map(|r| &r[..])
+    }
+
+    pub(super) fn iter_mut(&mut self) -> impl Iterator<Item = &mut [Cell]> {
+        self.rows.iter_mut().map(|r| &mut r[..])
+    }
+
+    pub(super) fn fill(&mut self, cell: Cell) {
+        self.iter_mut().for_each(|r| r.fill(cell));
+    }
+
+    /// The row buffer itself, for swapping with the scrollback. Callers
+    /// must leave it `cols` wide.
+    pub(super) fn row_mut(&mut self, y: usize) -> &mut Vec<Cell> {
+        &mut self.rows[y]
+    }
+
+    /// Move row `top` to `bottom`, shifting the rows in between up by one.
+    pub(super) fn rotate_up(&mut self, top: usize, bottom: usize) {
+        if top == 0 && bottom + 1 == self.rows.len() {
+            self.rows.rotate_left(1);
+        } else if let Some(row) = self.rows.remove(top) {
+            self.rows.insert(bottom, row);
+        }
+    }
+
+    /// Move row `bottom` to `top`, shifting the rows in between down by one.
+    pub(super) fn rotate_down(&mut self, top: usize, bottom: usize) {
+        if top == 0 && bottom + 1 == self.rows.len() {
+            self.rows.rotate_right(1);
+        } else if let Some(row) = self.rows.remove(bottom) {
+            self.rows.insert(top, row);
+        }
+    }
+}
+
+impl Index<usize> for Grid {
+    type Output = [Cell];
+
+    fn index(&self, y: usize) -> &[Cell] {
+        &self.rows[y]
+    }
+}
+
+impl IndexMut<usize> for Grid {
+    fn index_mut(&mut self, y: usize) -> &mut [Cell] {
+        &mut self.rows[y]
+    }
+}
@@ -0,0 +1,316 @@
+//! Scrollback storage, optionally spilling old rows to disk.
+//!
+//! Rows live in memory until spilling is enabled with [`History::spill_to`].
+//! From then on, whenever more than `KEEP_ROWS + PAGE_ROWS` rows are in
+//! memory the oldest `PAGE_ROWS` are deflated into a page file. Pages are
+//! read back on demand (a few decoded pages are cached), trimmed from the
+//! front as the scrollback limit evicts rows, and the directory is removed
+//! when the history is dropped. Row arrival times are small and always stay
+//! in memory.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, VecDeque};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use super::{Cell, StyleId};
+
+/// Rows per page file.
+const PAGE_ROWS: usize = 1024;
+/// Newest rows always kept in memory.
+const KEEP_ROWS: usize = 4096;
+/// Decoded pages kept around for scrolling and sequential reads.
+const CACHED_PAGES: usize = 4;
+
+/// Rows scrolled off the top of the screen, oldest first.
+pub struct History {
+    /// Newest rows; they follow the spilled ones.
+    rows: VecDeque<Vec<Cell>>,
+    /// Arrival time of every row, spilled or not (see `Emu::line_times`).
+    times: VecDeque<u64>,
+    spill: Option<Spill>,
+    cols: usize,
+}
+
+struct Spill {
+    dir: PathBuf,
+    /// Oldest first; every page holds `PAGE_ROWS` rows.
+    pages: VecDeque<Page>,
+    /// Rows of the front page already evicted.
+    skip: usize,
+    next_seq: u64,
+    /// A page could not be written; keep new rows in memory.
+    failed: bool,
+    cache: RefCell<VecDeque<(u64, Vec<Vec<Cell>>)>>,
+}
+
+struct Page {
+    seq: u64,
+    /// Styles used by the page, kept alive in the style table.
+    styles: Vec<StyleId>,
+}
+
+impl History {
+    pub(super) fn new(cols: usize) -> Self {
+        Self {
+            rows: VecDeque::new(),
+            times: VecDeque::new(),
+            spill: None,
+            cols,
+        }
+    }
+
+    /// Spill old rows into page files under `dir`, which is created now and
+    /// removed again when the history is dropped.
+    pub(super) fn spill_to(&mut self, dir: PathBuf) -> Result<()> {
+        self.clear();
+        self.drop_spill();
+        fs::create_dir_all(&dir).with_context(|| dir.display().to_string())?;
+        self.spill = Some(Spill {
+            dir,
+            pages: VecDeque::new(),
+            skip: 0,
+            next_seq: 0,
+            failed: false,
+            cache: RefCell::new(VecDeque::new()),
+        });
+        self.spill_full_pages();
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.spilled_rows() + self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether rows beyond the newest few thousand go to disk.
+    pub fn is_spilling(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Rows currently on disk.
+    pub fn spilled_rows(&self) -> usize {
+        self.spill
+            .as_ref()
+            .map_or(0, |s| s.pages.len() * PAGE_ROWS - s.skip)
+    }
+
+    /// Row `i`, 0 being the oldest. Spilled rows are read back from disk; a
+    /// page that cannot be read comes back blank.
+    pub fn get(&self, i: usize) -> Option<Cow<'_, [Cell]>> {
+        let spilled = self.spilled_rows();
+        if i >= spilled {
+            return self.rows.get(i - spilled).map(|r| Cow::Borrowed(&r[..]));
+        }
+        let spill = self.spill.as_ref()?;
+        let i = i + spill.skip;
+        let page = &spill.pages[i / PAGE_ROWS];
+        let mut row = match spill.row(page.seq, i % PAGE_ROWS) {
+            Ok(row) => row,
+            Err(e) => {
+                log::warn!("scrollback page {}: {e:#}", page.seq);
+                Vec::new()
+            }
+        };
+        row.resize(self.cols, Cell::BLANK);
+        Some(Cow::Owned(row))
+    }
+
+    /// Arrival time of row `i`; 0 if unknown or out of range.
+    pub fn time(&self, i: usize) -> u64 {
+        self.times.get(i).copied().unwrap_or(0)
+    }
+
+    pub fn back(&self) -> Option<Cow<'_, [Cell]>> {
+        self.len().checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    /// All rows, oldest first, paging in as needed.
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'_, [Cell]>> {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+
+    /// The rows held in memory, oldest first.
+    pub(super) fn hot_rows(&self) -> impl Iterator<Item = &[Cell]> {
+        self.rows.iter().map(|r| &r[..])
+    }
+
+    /// Styles referenced by spilled rows.
+    pub(super) fn spilled_styles(&self) -> impl Iterator<Item = StyleId> + '_ {
+        self.spill
+            .iter()
+            .flat_map(|s| s.pages.iter().flat_map(|p| p.styles.iter().copied()))
+    }
+
+    pub(super) fn push_back(&mut self, row: Vec<Cell>, time: u64) {
+        self.rows.push_back(row);
+        self.times.push_back(time);
+        self.spill_full_pages();
+    }
+
+    /// Drop the oldest row, returning its buffer when it was in memory.
+    pub(super) fn pop_front(&mut self) -> Option<Vec<Cell>> {
+        self.times.pop_front();
+        let Some(spill) = self.spill.as_mut().filter(|s| !s.pages.is_empty()) else {
+            return self.rows.pop_front();
+        };
+        spill.skip += 1;
+        if spill.skip == PAGE_ROWS {
+            spill.skip = 0;
+            if let Some(page) = spill.pages.pop_front() {
+                spill.remove(page.seq);
+            }
+        }
+        None
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.rows.clear();
+        self.times.clear();
+        if let Some(spill) = &mut self.spill {
+            for page in std::mem::take(&mut spill.pages) {
+                spill.remove(page.seq);
+            }
+            spill.skip = 0;
+        }
+    }
+
+    /// Pad or cut every row to `cols`; spilled rows are fitted as they are
+    /// read back.
+    pub(super) fn set_cols(&mut self, cols: usize) {
+        self.cols = cols;
+        for row in &mut self.rows {
+            row.resize(cols, Cell::BLANK);
+        }
+    }
+
+    pub(super) fn check_invariants(&self) {
+        assert_eq!(self.times.len(), self.len(), "scrollback times");
+        for row in &self.rows {
+            assert_eq!(row.len(), self.cols, "scrollback row width");
+        }
+    }
+
+    fn spill_full_pages(&mut self) {
+        let Some(spill) = self.spill.as_mut().filter(|s| !s.failed) else {
+            return;
+        };
+        while self.rows.len() >= KEEP_ROWS + PAGE_ROWS {
+            let page: Vec<Vec<Cell>> = self.rows.drain(..PAGE_ROWS).collect();
+            if let Err(e) = spill.write(&page) {
+                // Pages already written stay readable; new rows stay here.
+                log::warn!("scrollback spill stopped: {e:#}");
+                for row in page.into_iter().rev() {
+                    self.rows.push_front(row);
+                }
+                spill.failed = true;
+                return;
+            }
+        }
+    }
+
+    /// Stop spilling and remove the page directory, forgetting the rows on
+    /// disk.
+    pub(super) fn drop_spill(&mut self) {
+        if let Some(spill) = self.spill.take() {
+            let lost = spill.pages.len() * PAGE_ROWS - spill.skip;
+            self.times.drain(..lost);
+            if let Err(e) = fs::remove_dir_all(&spill.dir) {
+                log::debug!("removing {}: {e}", spill.dir.display());
+            }
+        }
+    }
+}
+
+impl Drop for History {
+    fn drop(&mut self) {
+        self.drop_spill();
+    }
+}
+
+impl Spill {
+    fn path(&self, seq: u64) -> PathBuf {
+        self.dir.join(format!("page-{seq}.bin"))
+    }
+
+    /// Page layout before deflate: row count and width as little-endian
+    /// u32s, then each cell as its char and style id.
+    fn write(&mut self, rows: &[Vec<Cell>]) -> Result<()> {
+        let cols = rows.first().map_or(0, Vec::len);
+        let mut raw = Vec::with_capacity(8 + rows.len() * cols * 8);
+        raw.extend_from_slice(&(rows.len() as u32).to_le_bytes());
+        raw.extend_from_slice(&(cols as u32).to_le_bytes());
+        let mut styles = BTreeSet::new();
+        for cell in rows.iter().flatten() {
+            raw.extend_from_slice(&(cell.ch as u32).to_le_bytes());
+            raw.extend_from_slice(&cell.style.0.to_le_bytes());
+            styles.insert(cell.style.0);
+        }
+        let mut enc = DeflateEncoder::new(Vec::new(), Compression::fast());
+        enc.write_all(&raw)?;
+        let seq = self.next_seq;
+        let path = self.path(seq);
+        fs::write(&path, enc.finish()?).with_context(|| path.display().to_string())?;
+        self.next_seq += 1;
+        self.pages.push_back(Page {
+            seq,
+            styles: styles.into_iter().map(StyleId).collect(),
+        });
+        Ok(())
+    }
+
+    fn read(&self, seq: u64) -> Result<Vec<Vec<Cell>>> {
+        let path = self.path(seq);
+        let raw = fs::read(&path).with_context(|| path.display().to_string())?;
+        let mut data = Vec::new();
+        DeflateDecoder::new(&raw[..]).read_to_end(&mut data)?;
+        let word = |i: usize| -> Option<u32> {
+            let b = data.get(i * 4..i * 4 + 4)?;
+            Some(u32::from_le_bytes(b.try_into().ok()?))
+        };
+        let (Some(rows), Some(cols)) = (word(0), word(1)) else {
+            bail!("truncated page");
+        };
+        let (rows, cols) = (rows as usize, cols as usize);
+        if data.len() != 8 + rows * cols * 8 {
+            bail!("page size mismatch");
+        }
+        let cell = |i: usize| Cell {
+            ch: word(2 + i * 2).and_then(char::from_u32).unwrap_or(' '),
+            style: StyleId(word(3 + i * 2).unwrap_or(0)),
+        };
+        Ok((0..rows)
+            .map(|y| (0..cols).map(|x| cell(y * cols + x)).collect())
+            .collect())
+    }
+
+    fn row(&self, seq: u64, y: usize) -> Result<Vec<Cell>> {
+        let mut cache = self.cache.borrow_mut();
+        if let Some(pos) = cache.iter().position(|(s, _)| *s == seq) {
+            let entry = cache.remove(pos).expect("cached page");
+            cache.push_front(entry);
+        } else {
+            let page = self.read(seq)?;
+            cache.push_front((seq, page));
+            cache.truncate(CACHED_PAGES);
+        }
+        Ok(cache[0].1.get(y).cloned().unwrap_or_default())
+    }
+
+    fn remove(&mut self, seq: u64) {
+        self.cache.get_mut().retain(|(s, _)| *s != seq);
+        if let Err(e) = fs::remove_file(self.path(seq)) {
+            log::debug!("removing scrollback page {seq}: {e}");
+        }
+    }
+}
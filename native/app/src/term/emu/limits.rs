@@ -0,0 +1,107 @@
+//! Bounds on what terminal output can make the emulator do.
+//!
+//! Output comes from files we `cat` and hosts we connect to, so none of it
+//! is trusted. The parser buffers a whole OSC string before dispatching
+//! it, a title is handed to the window system on every change, and wide
+//! rows make scrollback expensive; each of these is capped here.
+
+use serde::Deserialize;
+
+use super::trace::SeqKind;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// Longest OSC string in bytes. Longer ones are dropped unread.
+    pub osc_bytes: usize,
+    /// Longest DCS string in bytes. Longer ones are dropped.
+    pub dcs_bytes: usize,
+    /// Titles are cut to this many characters.
+    pub title_chars: usize,
+    /// Title changes applied per second; of the rest only the newest is
+    /// kept, and applied when the second is up.
+    pub titles_per_sec: u32,
+    /// Memory the scrollback may use, in MiB. Wide rows lower the number of
+    /// rows kept below `scrollback_limit`; rows spilled to disk only count
+    /// their timestamp.
+    pub scrollback_mib: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            osc_bytes: 1 << 20,
+            dcs_bytes: 1 << 20,
+            title_chars: 256,
+            titles_per_sec: 10,
+            scrollback_mib: 256,
+        }
+    }
+}
+
+pub(super) enum Step {
+    /// Hand the byte to the parser.
+    Feed,
+    /// Swallow the byte.
+    Skip,
+    /// The open string just grew past its limit; drop what the parser has
+    /// and swallow the rest.
+    Overflow { kind: SeqKind, max: usize },
+}
+
+/// Follows OSC and DCS strings through the byte stream to measure them.
+/// An OSC starts with `ESC ]` and a DCS with `ESC P`; ESC, CAN and SUB end
+/// either, and BEL also ends an OSC.
+#[derive(Default)]
+pub(super) struct Strings {
+    open: Option<SeqKind>,
+    len: usize,
+    dropping: bool,
+    after_esc: bool,
+}
+
+impl Strings {
+    pub(super) fn step(&mut self, b: u8, limits: &Limits) -> Step {
+        let after_esc = std::mem::replace(&mut self.after_esc, b == 0x1b);
+        let ends = matches!(b, 0x1b | 0x18 | 0x1a) || b == 0x07 && self.open == Some(SeqKind::Osc);
+        if ends {
+            self.open = None;
+            // ESC may start the next sequence; the others only terminate.
+            return if std::mem::take(&mut self.dropping) && b != 0x1b {
+                Step::Skip
+            } else {
+                Step::Feed
+            };
+        }
+        if after_esc {
+            self.open = match b {
+                b']' => Some(SeqKind::Osc),
+                b'P' => Some(SeqKind::Dcs),
+                _ => None,
+            };
+            self.len = 0;
+            return Step::Feed;
+        }
+        let Some(kind) = self.open else {
+            return Step::Feed;
+        };
+        if self.dropping {
+            return Step::Skip;
+        }
+        self.len += 1;
+        let max = match kind {
+            SeqKind::Osc => limits.osc_bytes,
+            _ => limits.dcs_bytes,
+        };
+        if self.len > max {
+            self.dropping = true;
+            return Step::Overflow { kind, max };
+        }
+        Step::Feed
+    }
+
+    /// Forget any open string, e.g. after the parser was reset.
+    pub(super) fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,227 @@
+//! VT400 rectangular area operations (DECFRA, DECERA, DECSERA, DECCRA,
+//! DECCARA, DECRARA) and selective erase (DECSED, DECSEL).
+
+use super::style::Styles;
+use super::{Cell, Emu, Flags, Style, Underline};
+use vte::Params;
+
+/// Inclusive, 0-based screen rectangle.
+#[derive(Clone, Copy)]
+pub(super) struct Rect {
+    top: usize,
+    left: usize,
+    bottom: usize,
+    right: usize,
+}
+
+impl Emu {
+    /// Read `Pt;Pl;Pb;Pr` starting at parameter `first`. Coordinates are
+    /// relative to the scroll region in origin mode and clamped to the screen.
+    /// A `stream` extent only needs its start to precede its end.
+    pub(super) fn rect_arg(&self, params: &Params, first: usize, stream: bool) -> Option<Rect> {
+        let origin = if self.origin_mode { self.scroll_top } else { 0 };
+        let get = |i: usize, default: usize| match params.iter().nth(first + i).map(|p| p[0]) {
+            None | Some(0) => default,
+            Some(v) => v as usize,
+        };
+        let top = get(0, 1) - 1 + origin;
+        let left = (get(1, 1) - 1).min(self.cols - 1);
+        let bottom = (get(2, self.rows) - 1 + origin).min(self.rows - 1);
+        let right = (get(3, self.cols) - 1).min(self.cols - 1);
+        let ordered = if stream {
+            top < bottom || (top == bottom && left <= right)
+        } else {
+            top <= bottom && left <= right
+        };
+        ordered.then_some(Rect {
+            top,
+            left,
+            bottom,
+            right,
+        })
+    }
+
+    fn for_each_in(&mut self, r: Rect, mut f: impl FnMut(&mut Cell, &mut Styles)) {
+        for y in r.top..=r.bottom {
+            for cell in &mut self.grid[y][r.left..=r.right] {
+                f(cell, &mut self.styles);
+            }
+        }
+    }
+
+    /// Cells touched by DECCARA/DECRARA: the rectangle, or with DECSACE 0/1
+    /// the character stream from the top-left to the bottom-right corner.
+    fn for_each_in_extent(&mut self, r: Rect, f: impl FnMut(&mut Cell, &mut Styles)) {
+        if self.rect_extent {
+            return self.for_each_in(r, f);
+        }
+        self.for_each_in_stream(r, f);
+    }
+
+    /// Cells from the top-left to the bottom-right corner in reading order.
+    fn for_each_in_stream(&mut self, r: Rect, mut f: impl FnMut(&mut Cell, &mut Styles)) {
+        for y in r.top..=r.bottom {
+            let from = if y == r.top { r.left } else { 0 };
+            let to = if y == r.bottom {
+                r.right
+            } else {
+                self.cols - 1
+            };
+            for cell in &mut self.grid[y][from..=to] {
+                f(cell, &mut self.styles);
+            }
+        }
+    }
+
+    /// DECFRA: `CSI Pch ; Pt ; Pl ; Pb ; Pr $ x`.
+    pub(super) fn fill_rect(&mut self, params: &Params) {
+        let ch = params.iter().next().map(|p| p[0]).unwrap_or(0) as u32;
+        let ch = match ch {
+            32..=126 | 160..=255 => char::from_u32(ch).unwrap_or(' '),
+            _ => return,
+        };
+        let Some(r) = self.rect_arg(params, 1, false) else {
+            return;
+        };
+        let cell = Cell {
+            ch,
+            style: self.pen_id,
+        };
+        self.for_each_in(r, |c, _| *c = cell);
+    }
+
+    /// DECERA: `CSI Pt ; Pl ; Pb ; Pr $ z`; erases protected cells too.
+    pub(super) fn erase_rect(&mut self, params: &Params) {
+        if let Some(r) = self.rect_arg(params, 0, false) {
+            self.for_each_in(r, |c, _| *c = Cell::BLANK);
+        }
+    }
+
+    /// DECSERA: `CSI Pt ; Pl ; Pb ; Pr $ {`; leaves protected cells alone.
+    pub(super) fn selective_erase_rect(&mut self, params: &Params) {
+        if let Some(r) = self.rect_arg(params, 0, false) {
+            self.for_each_in(r, |c, styles| selective_erase(c, styles));
+        }
+    }
+
+    /// DECCRA: `CSI Pts ; Pls ; Pbs ; Prs ; Pps ; Ptd ; Pld ; Ppd $ v`.
+    /// We have a single page, so the page numbers are ignored.
+    pub(super) fn copy_rect(&mut self, params: &Params) {
+        let Some(src) = self.rect_arg(params, 0, false) else {
+            return;
+        };
+        let origin = if self.origin_mode { self.scroll_top } else { 0 };
+        let get = |i: usize| match params.iter().nth(i).map(|p| p[0]) {
+            None | Some(0) => 1,
+            Some(v) => v as usize,
+        };
+        let dst_top = get(5) - 1 + origin;
+        let dst_left = get(6) - 1;
+        if dst_top >= self.rows || dst_left >= self.cols {
+            return;
+        }
+        let h = (src.bottom - src.top + 1).min(self.rows - dst_top);
+        let w = (src.right - src.left + 1).min(self.cols - dst_left);
+        // Copy through a buffer so overlapping source/destination work.
+        let mut buf = Vec::with_capacity(w * h);
+        for y in 0..h {
+            buf.extend_from_slice(&self.grid[src.top + y][src.left..src.left + w]);
+        }
+        for (y, row) in buf.chunks(w).enumerate() {
+            self.grid[dst_top + y][dst_left..dst_left + w].copy_from_slice(row);
+        }
+    }
+
+    /// DECCARA (`$ r`) sets and DECRARA (`$ t`) toggles the SGR subset
+    /// bold/underline/blink/inverse over an area.
+    pub(super) fn change_rect_attrs(&mut self, params: &Params, reverse: bool) {
+        let Some(r) = self.rect_arg(params, 0, !self.rect_extent) else {
+            return;
+        };
+        let ops: Vec<u16> = params.iter().skip(4).map(|p| p[0]).collect();
+        let ops = if ops.is_empty() { vec![0] } else { ops };
+        self.for_each_in_extent(r, |c, styles| {
+            let mut style = *styles.get(c.style);
+            for &op in &ops {
+                apply_rect_sgr(&mut style, op, reverse);
+            }
+            c.style = styles.intern(style);
+        });
+    }
+
+    /// DECSED: `CSI ? Ps J`.
+    pub(super) fn selective_erase_display(&mut self, mode: u16) {
+        let (x, y) = (self.cur_x.min(self.cols - 1), self.cur_y);
+        let (first, last) = match mode {
+            0 => ((x, y), (self.cols - 1, self.rows - 1)),
+            1 => ((0, 0), (x, y)),
+            2 => ((0, 0), (self.cols - 1, self.rows - 1)),
+            _ => return,
+        };
+        let stream = Rect {
+            top: first.1,
+            left: first.0,
+            bottom: last.1,
+            right: last.0,
+        };
+        self.for_each_in_stream(stream, |c, styles| selective_erase(c, styles));
+    }
+
+    /// DECSEL: `CSI ? Ps K`.
+    pub(super) fn selective_erase_line(&mut self, mode: u16) {
+        let x = self.cur_x.min(self.cols - 1);
+        let range = match mode {
+            0 => x..self.cols,
+            1 => 0..x + 1,
+            2 => 0..self.cols,
+            _ => return,
+        };
+        for c in &mut self.grid[self.cur_y][range] {
+            selective_erase(c, &self.styles);
+        }
+    }
+}
+
+fn selective_erase(c: &mut Cell, styles: &Styles) {
+    if !styles.get(c.style).flags.contains(Flags::PROTECTED) {
+        c.ch = ' ';
+    }
+}
+
+fn apply_rect_sgr(c: &mut Style, op: u16, reverse: bool) {
+    let toggle = |flags: &mut Flags, f: Flags| {
+        let on = !reverse || !flags.contains(f);
+        flags.set(f, on);
+    };
+    match op {
+        0 if reverse => {
+            for f in [Flags::BOLD, Flags::BLINK, Flags::INVERSE] {
+                toggle(&mut c.flags, f);
+            }
+            c.underline = match c.underline {
+                Underline::None => Underline::Single,
+                _ => Underline::None,
+            };
+        }
+        0 => {
+            for f in [Flags::BOLD, Flags::BLINK, Flags::INVERSE] {
+                c.flags.set(f, false);
+            }
+            c.underline = Underline::None;
+        }
+        1 => toggle(&mut c.flags, Flags::BOLD),
+        4 => {
+            c.underline = match (reverse, c.underline) {
+                (true, Underline::None) | (false, _) => Underline::Single,
+                (true, _) => Underline::None,
+            };
+        }
+        5 => toggle(&mut c.flags, Flags::BLINK),
+        7 => toggle(&mut c.flags, Flags::INVERSE),
+        22 if !reverse => c.flags.set(Flags::BOLD, false),
+        24 if !reverse => c.underline = Underline::None,
+        25 if !reverse => c.flags.set(Flags::BLINK, false),
+        27 if !reverse => c.flags.set(Flags::INVERSE, false),
+        _ => {}
+    }
+}
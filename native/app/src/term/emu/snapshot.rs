@@ -0,0 +1,295 @@
+//! Versioned, serde-based snapshots of the emulator state.
+//!
+//! The visible screen is stored as readable rows (text plus attribute runs)
+//! so a snapshot attached to a bug report can be inspected by hand. The
+//! scrollback is deflated and trimmed from the oldest end until it fits the
+//! caller's byte budget.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use super::style::Styles;
+use super::{is_default, Cell, CursorStyle, Emu, History, LineAttr, SavedCursor, Style, StyleId};
+
+/// Bumped whenever the format changes incompatibly.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub cols: usize,
+    pub rows: usize,
+    pub screen: Vec<Row>,
+    pub line_attrs: Vec<LineAttr>,
+    pub scrollback: Scrollback,
+    pub cursor: CursorState,
+    pub modes: Modes,
+    /// Current SGR state.
+    pub pen: Style,
+    saved: Option<SavedCursor>,
+    pub title: String,
+    pub cwd: Option<String>,
+}
+
+/// One row as text plus runs of attributes; runs cover the row left to right
+/// and are omitted entirely for an unstyled row.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Row {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<Run>,
+    /// Arrival time in milliseconds since the Unix epoch, if known.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub time: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Run {
+    pub len: usize,
+    #[serde(flatten)]
+    pub style: Style,
+}
+
+/// Deflated JSON array of [`Row`]s, oldest first, base64 encoded.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scrollback {
+    pub rows: usize,
+    pub data: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CursorState {
+    pub x: usize,
+    pub y: usize,
+    pub visible: bool,
+    pub style: CursorStyle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Modes {
+    pub origin: bool,
+    pub autowrap: bool,
+    pub insert: bool,
+    pub rect_extent: bool,
+    pub scroll_top: usize,
+    pub scroll_bottom: usize,
+}
+
+impl Row {
+    fn encode(cells: &[Cell], time: u64, styles: &Styles) -> Self {
+        let text = cells.iter().map(|c| c.ch).collect();
+        let mut runs: Vec<Run> = Vec::new();
+        for cell in cells {
+            let style = *styles.get(cell.style);
+            match runs.last_mut() {
+                Some(run) if run.style == style => run.len += 1,
+                _ => runs.push(Run { len: 1, style }),
+            }
+        }
+        if runs.iter().all(|r| r.style == Style::default()) {
+            runs.clear();
+        }
+        Self { text, runs, time }
+    }
+
+    /// Expand back into exactly `cols` cells, padding or truncating.
+    fn decode(&self, cols: usize, styles: &mut Styles) -> Vec<Cell> {
+        let ids: Vec<StyleId> = self.runs.iter().map(|r| styles.intern(r.style)).collect();
+        let ids = self
+            .runs
+            .iter()
+            .zip(ids)
+            .flat_map(|(r, id)| std::iter::repeat_n(id, r.len))
+            .chain(std::iter::repeat(StyleId::DEFAULT));
+        let mut cells: Vec<Cell> = self
+            .text
+            .chars()
+            .zip(ids)
+            .take(cols)
+            .map(|(ch, style)| Cell { ch, style })
+            .collect();
+        cells.resize(cols, Cell::BLANK);
+        cells
+    }
+}
+
+impl Scrollback {
+    /// Compress as many of the newest `rows` as fit in `budget` bytes.
+    fn compress(rows: &History, styles: &Styles, budget: usize) -> Result<Self> {
+        let mut keep = rows.len();
+        loop {
+            let encoded: Vec<Row> = (rows.len() - keep..rows.len())
+                .filter_map(|i| Some(Row::encode(&rows.get(i)?, rows.time(i), styles)))
+                .collect();
+            let mut enc = DeflateEncoder::new(Vec::new(), Compression::default());
+            enc.write_all(&serde_json::to_vec(&encoded)?)?;
+            let data = B64.encode(enc.finish()?);
+            if data.len() <= budget || keep == 0 {
+                return Ok(Self { rows: keep, data });
+            }
+            // Guess from the current ratio, always making progress.
+            let fit = (keep as u128 * budget as u128 / data.len() as u128) as usize;
+            keep = (fit * 9 / 10).min(keep - 1);
+        }
+    }
+
+    fn decompress(&self) -> Result<Vec<Row>> {
+        if self.rows == 0 {
+            return Ok(Vec::new());
+        }
+        let raw = B64.decode(&self.data).context("scrollback is not base64")?;
+        let mut json = Vec::new();
+        DeflateDecoder::new(&raw[..])
+            .read_to_end(&mut json)
+            .context("scrollback is not deflate data")?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse a snapshot, rejecting versions this build does not understand.
+    pub fn from_json(data: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(data)?;
+        let version = value.get("version").and_then(|v| v.as_u64());
+        if version != Some(SNAPSHOT_VERSION as u64) {
+            bail!("unsupported snapshot version {version:?} (expected {SNAPSHOT_VERSION})");
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut file = fs::File::create(path)?;
+        file.write_all(self.to_json()?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)?;
+        Self::from_json(&data).with_context(|| format!("snapshot {}", path.display()))
+    }
+}
+
+impl Emu {
+    /// Capture the session. The compressed scrollback is kept under
+    /// `scrollback_budget` bytes by dropping its oldest rows.
+    pub fn snapshot(&self, scrollback_budget: usize) -> Result<Snapshot> {
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            cols: self.cols,
+            rows: self.rows,
+            screen: self
+                .grid
+                .iter()
+                .zip(&self.line_times)
+                .map(|(r, &time)| Row::encode(r, time, &self.styles))
+                .collect(),
+            line_attrs: self.line_attrs.clone(),
+            scrollback: Scrollback::compress(&self.scrollback, &self.styles, scrollback_budget)?,
+            cursor: CursorState {
+                x: self.cur_x,
+                y: self.cur_y,
+                visible: self.cursor_visible,
+                style: self.cursor_style,
+            },
+            modes: Modes {
+                origin: self.origin_mode,
+                autowrap: self.autowrap,
+                insert: self.insert_mode,
+                rect_extent: self.rect_extent,
+                scroll_top: self.scroll_top,
+                scroll_bottom: self.scroll_bottom,
+            },
+            pen: self.pen,
+            saved: self.saved,
+            title: self.title.clone(),
+            cwd: self.cwd.clone(),
+        })
+    }
+
+    /// Load `snap` into this emulator, keeping its current size, listener
+    /// and policies. A snapshot taken at another size is fitted: rows are
+    /// padded or cut, and screen rows that do not fit move to the scrollback.
+    pub fn restore(&mut self, snap: &Snapshot) -> Result<()> {
+        if snap.version != SNAPSHOT_VERSION {
+            bail!("unsupported snapshot version {}", snap.version);
+        }
+        let (cols, rows) = (self.cols, self.rows);
+        // Every cell is replaced, so the style table can start over.
+        let mut styles = Styles::new();
+        let mut history: VecDeque<(Vec<Cell>, u64)> = snap
+            .scrollback
+            .decompress()?
+            .iter()
+            .map(|r| (r.decode(cols, &mut styles), r.time))
+            .collect();
+        let overflow = snap.screen.len().saturating_sub(rows);
+        history.extend(
+            snap.screen[..overflow]
+                .iter()
+                .map(|r| (r.decode(cols, &mut styles), r.time)),
+        );
+        while history.len() > self.scrollback_capacity() {
+            history.pop_front();
+        }
+
+        self.grid.fill(Cell::BLANK);
+        self.line_attrs.fill(LineAttr::Normal);
+        self.line_times.fill(0);
+        for (y, row) in snap.screen[overflow..].iter().enumerate() {
+            *self.grid.row_mut(y) = row.decode(cols, &mut styles);
+            self.line_times[y] = row.time;
+            self.line_attrs[y] = snap
+                .line_attrs
+                .get(y + overflow)
+                .copied()
+                .unwrap_or_default();
+        }
+        self.scrollback.clear();
+        for (row, time) in history {
+            self.scrollback.push_back(row, time);
+        }
+        self.styles = styles;
+        self.display_offset = 0;
+
+        self.cur_x = snap.cursor.x.min(cols);
+        self.cur_y = snap.cursor.y.saturating_sub(overflow).min(rows - 1);
+        self.cursor_visible = snap.cursor.visible;
+        self.cursor_style = snap.cursor.style;
+        self.origin_mode = snap.modes.origin;
+        self.autowrap = snap.modes.autowrap;
+        self.insert_mode = snap.modes.insert;
+        self.rect_extent = snap.modes.rect_extent;
+        if (snap.cols, snap.rows) == (cols, rows)
+            && snap.modes.scroll_top < snap.modes.scroll_bottom
+            && snap.modes.scroll_bottom < rows
+        {
+            self.scroll_top = snap.modes.scroll_top;
+            self.scroll_bottom = snap.modes.scroll_bottom;
+        } else {
+            self.scroll_top = 0;
+            self.scroll_bottom = rows - 1;
+        }
+        self.pen = snap.pen;
+        self.update_pen();
+        self.saved = snap.saved;
+        self.title = snap.title.clone();
+        self.cwd = snap.cwd.clone();
+        self.parser = Default::default();
+        self.strings.reset();
+        self.ground = true;
+        Ok(())
+    }
+}
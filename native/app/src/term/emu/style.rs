@@ -0,0 +1,93 @@
+//! Interned cell styles.
+//!
+//! Cells store a [`StyleId`] instead of their colors and attributes. A
+//! screen rarely uses more than a handful of distinct styles, but programs
+//! drawing true-color gradients can mint thousands, so once the table
+//! doubles past the number of styles still in use the unused ones are
+//! freed for reuse. Ids are never renumbered, which lets scrollback pages
+//! spilled to disk keep theirs.
+
+use std::collections::HashMap;
+
+use super::{Emu, Style, StyleId};
+
+/// Table size below which we never bother compacting.
+const MIN_COMPACT: usize = 1024;
+
+pub(super) struct Styles {
+    table: Vec<Style>,
+    ids: HashMap<Style, StyleId>,
+    /// Slots whose style nothing refers to any more.
+    free: Vec<StyleId>,
+    /// Live style count at which the next compaction is due.
+    compact_at: usize,
+}
+
+impl Styles {
+    pub(super) fn new() -> Self {
+        let mut styles = Self {
+            table: Vec::new(),
+            ids: HashMap::new(),
+            free: Vec::new(),
+            compact_at: MIN_COMPACT,
+        };
+        styles.intern(Style::default());
+        styles
+    }
+
+    /// Slots in the table, live or free; every valid id is below this.
+    pub(super) fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub(super) fn get(&self, id: StyleId) -> &Style {
+        &self.table[id.0 as usize]
+    }
+
+    pub(super) fn intern(&mut self, style: Style) -> StyleId {
+        if let Some(&id) = self.ids.get(&style) {
+            return id;
+        }
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.table[id.0 as usize] = style;
+                id
+            }
+            None => {
+                self.table.push(style);
+                StyleId(self.table.len() as u32 - 1)
+            }
+        };
+        self.ids.insert(style, id);
+        id
+    }
+
+    pub(super) fn needs_compaction(&self) -> bool {
+        self.ids.len() >= self.compact_at
+    }
+}
+
+impl Emu {
+    /// Free the styles no cell, scrollback page or pen refers to any more.
+    pub(super) fn compact_styles(&mut self) {
+        let mut live = vec![false; self.styles.len()];
+        live[StyleId::DEFAULT.0 as usize] = true;
+        live[self.pen_id.0 as usize] = true;
+        let rows = self.grid.iter().chain(self.scrollback.hot_rows());
+        for cell in rows.flatten() {
+            live[cell.style.0 as usize] = true;
+        }
+        for id in self.scrollback.spilled_styles() {
+            live[id.0 as usize] = true;
+        }
+        let styles = &mut self.styles;
+        let before = styles.ids.len();
+        styles.ids.retain(|_, id| live[id.0 as usize]);
+        styles.free = (0..live.len() as u32)
+            .filter(|&i| !live[i as usize])
+            .map(StyleId)
+            .collect();
+        styles.compact_at = (styles.ids.len() * 2).max(MIN_COMPACT);
+        log::debug!("compacted styles: {before} -> {}", styles.ids.len());
+    }
+}
@@ -0,0 +1,144 @@
+//! A record of what the parser dispatched, for the debug inspector.
+//!
+//! Counters for sequences the emulator does not act on are always kept;
+//! the log of recent sequences is off until [`Emu::set_trace`] gives it a
+//! capacity, so the hot path pays nothing for it.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+
+use vte::Params;
+
+use super::Emu;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeqKind {
+    Csi,
+    Osc,
+    Dcs,
+    Esc,
+}
+
+/// One dispatched sequence, written roughly as it appeared on the wire:
+/// `CSI ?1049h`, `OSC 0;title`, `ESC (0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub kind: SeqKind,
+    pub text: String,
+    /// False when the emulator ignored the sequence, or only part of it.
+    pub handled: bool,
+}
+
+/// Sequences seen but not acted on since the emulator was created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Unknown {
+    pub csi: u64,
+    pub osc: u64,
+    pub dcs: u64,
+    pub esc: u64,
+}
+
+/// Longest OSC payload kept in a log entry, in characters.
+const OSC_TEXT_MAX: usize = 60;
+
+pub(super) struct Trace {
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+}
+
+impl Trace {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+impl Emu {
+    /// Keep a log of the last `capacity` sequences; 0 stops logging and
+    /// drops the log.
+    pub fn set_trace(&mut self, capacity: usize) {
+        self.trace = (capacity > 0).then(|| Trace::new(capacity));
+    }
+
+    /// The logged sequences, oldest first. Empty while logging is off.
+    pub fn trace(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.trace.iter().flat_map(|t| t.entries.iter())
+    }
+
+    /// Count a dispatched sequence and log it if logging is on. Handlers
+    /// flag sequences they ignore by setting `unhandled`; this clears it.
+    pub(super) fn note(&mut self, kind: SeqKind, text: impl FnOnce() -> String) {
+        let handled = !std::mem::take(&mut self.unhandled);
+        if !handled {
+            let n = match kind {
+                SeqKind::Csi => &mut self.unknown.csi,
+                SeqKind::Osc => &mut self.unknown.osc,
+                SeqKind::Dcs => &mut self.unknown.dcs,
+                SeqKind::Esc => &mut self.unknown.esc,
+            };
+            *n += 1;
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry {
+                kind,
+                text: text(),
+                handled,
+            });
+        }
+    }
+}
+
+/// `CSI`/`DCS` introducer, private markers, parameters (sub-parameters
+/// joined with `:`), the remaining intermediates and the final character.
+/// The parser reports a missing parameter as a single 0, which is left out.
+pub(super) fn csi_text(intro: &str, params: &Params, ints: &[u8], action: char) -> String {
+    let (private, rest): (Vec<u8>, Vec<u8>) = ints.iter().partition(|b| (b'<'..=b'?').contains(b));
+    let mut out = format!("{intro} {}", String::from_utf8_lossy(&private));
+    let bare = params.len() == 1 && params.iter().next() == Some(&[0][..]);
+    for (i, p) in params.iter().enumerate().filter(|_| !bare) {
+        if i > 0 {
+            out.push(';');
+        }
+        for (j, v) in p.iter().enumerate() {
+            if j > 0 {
+                out.push(':');
+            }
+            let _ = write!(out, "{v}");
+        }
+    }
+    out.push_str(&String::from_utf8_lossy(&rest));
+    out.push(action);
+    out
+}
+
+/// `OSC` and its parameters, with controls escaped and long payloads cut.
+pub(super) fn osc_text(params: &[&[u8]]) -> String {
+    let joined = params.join(&b';');
+    let text = String::from_utf8_lossy(&joined);
+    let mut out = String::from("OSC ");
+    for (i, c) in text.chars().enumerate() {
+        if i == OSC_TEXT_MAX {
+            out.push('…');
+            break;
+        }
+        if c.is_control() {
+            out.extend(c.escape_default());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub(super) fn esc_text(ints: &[u8], byte: u8) -> String {
+    format!("ESC {}{}", String::from_utf8_lossy(ints), byte as char)
+}
@@ -0,0 +1,123 @@
+//! How many columns a character takes.
+//!
+//! Hosts disagree: CJK locales count East Asian Ambiguous characters as
+//! wide, older `wcwidth` tables predate wide emoji, and shells that use
+//! `wcwidth` count an emoji variation selector as nothing. The emulator lays
+//! out cells with a [`WidthPolicy`] and the renderer and exports measure
+//! with the same one, so they always agree with each other and can be made
+//! to agree with the host.
+
+use serde::Deserialize;
+use unicode_width::UnicodeWidthChar;
+
+/// VS15: text presentation for the preceding character.
+pub const VS15: char = '\u{fe0e}';
+/// VS16: emoji presentation for the preceding character.
+pub const VS16: char = '\u{fe0f}';
+
+/// Unicode version of the built-in width table.
+pub const TABLE_VERSION: (u8, u8, u8) = unicode_width::UNICODE_VERSION;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct WidthPolicy {
+    /// East Asian Ambiguous characters (Greek, Cyrillic, many symbols) take
+    /// two columns, as CJK fonts draw them.
+    pub ambiguous_wide: bool,
+    /// A narrow character followed by VS16 grows to two columns in emoji
+    /// presentation. Off, the selector is dropped and the character keeps
+    /// one column, which is how `wcwidth` counts it.
+    pub emoji_vs16: bool,
+    /// Unicode version the host's width table follows. From 9 on the
+    /// built-in table ([`TABLE_VERSION`]) applies; below 9, emoji that only
+    /// became wide in Unicode 9 take one column.
+    pub unicode_version: u32,
+}
+
+impl Default for WidthPolicy {
+    fn default() -> Self {
+        Self {
+            ambiguous_wide: false,
+            emoji_vs16: true,
+            unicode_version: TABLE_VERSION.0 as u32,
+        }
+    }
+}
+
+impl WidthPolicy {
+    /// Columns `c` takes on its own: 1 or 2. Zero-width characters still
+    /// occupy a cell of their own, so they count as 1.
+    pub fn of(&self, c: char) -> usize {
+        let w = if self.ambiguous_wide {
+            c.width_cjk()
+        } else {
+            c.width()
+        };
+        match w.unwrap_or(1) {
+            0 | 1 => 1,
+            _ if self.unicode_version < 9 && is_emoji_presentation(c) => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Characters with Emoji_Presentation, which Unicode 9 made wide.
+fn is_emoji_presentation(c: char) -> bool {
+    const RANGES: &[(u32, u32)] = &[
+        (0x231a, 0x231b),
+        (0x23e9, 0x23ec),
+        (0x23f0, 0x23f0),
+        (0x23f3, 0x23f3),
+        (0x25fd, 0x25fe),
+        (0x2614, 0x2615),
+        (0x2648, 0x2653),
+        (0x267f, 0x267f),
+        (0x2693, 0x2693),
+        (0x26a1, 0x26a1),
+        (0x26aa, 0x26ab),
+        (0x26bd, 0x26be),
+        (0x26c4, 0x26c5),
+        (0x26ce, 0x26ce),
+        (0x26d4, 0x26d4),
+        (0x26ea, 0x26ea),
+        (0x26f2, 0x26f3),
+        (0x26f5, 0x26f5),
+        (0x26fa, 0x26fa),
+        (0x26fd, 0x26fd),
+        (0x2705, 0x2705),
+        (0x270a, 0x270b),
+        (0x2728, 0x2728),
+        (0x274c, 0x274c),
+        (0x274e, 0x274e),
+        (0x2753, 0x2755),
+        (0x2757, 0x2757),
+        (0x2795, 0x2797),
+        (0x27b0, 0x27b0),
+        (0x27bf, 0x27bf),
+        (0x2b1b, 0x2b1c),
+        (0x2b50, 0x2b50),
+        (0x2b55, 0x2b55),
+        (0x1f004, 0x1f004),
+        (0x1f0cf, 0x1f0cf),
+        (0x1f18e, 0x1f18e),
+        (0x1f191, 0x1f19a),
+        (0x1f1e6, 0x1f1ff),
+        (0x1f300, 0x1f64f),
+        (0x1f680, 0x1f6ff),
+        (0x1f7e0, 0x1f7eb),
+        (0x1f90c, 0x1f9ff),
+        (0x1fa70, 0x1faff),
+    ];
+    let c = c as u32;
+    RANGES
+        .binary_search_by(|&(lo, hi)| {
+            if hi < c {
+                std::cmp::Ordering::Less
+            } else if lo > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
@@ -0,0 +1,115 @@
+use crossbeam_channel::Sender;
+
+/// Which clipboard an OSC 52 request targets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clipboard {
+    /// `c`: the regular clipboard.
+    Clipboard,
+    /// `p` / `s`: the primary selection.
+    Selection,
+}
+
+/// Shell integration marks (OSC 133 / FinalTerm).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PromptMark {
+    PromptStart,
+    CommandStart,
+    OutputStart,
+    CommandEnd { exit_code: Option<i32> },
+}
+
+/// ConEmu-style progress state (OSC 9;4).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProgressState {
+    #[default]
+    None,
+    Normal,
+    Error,
+    Indeterminate,
+    Paused,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub state: ProgressState,
+    /// 0..=100; meaningless for `None` and `Indeterminate`.
+    pub percent: u8,
+}
+
+/// XTWINOPS window manipulation (`CSI Ps ; ... t`). Only emitted when the
+/// emulator's `allow_window_ops` policy is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowOp {
+    Deiconify,
+    Iconify,
+    Move {
+        x: i32,
+        y: i32,
+    },
+    /// Text area size in pixels; `None` keeps the current value.
+    ResizePixels {
+        width: Option<u32>,
+        height: Option<u32>,
+    },
+    Raise,
+    Lower,
+    /// Text area size in cells; `None` keeps the current value.
+    ResizeCells {
+        cols: Option<u16>,
+        rows: Option<u16>,
+    },
+    Maximize(bool),
+    /// `None` toggles.
+    Fullscreen(Option<bool>),
+}
+
+/// Side effects the emulator cannot handle itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EmuEvent {
+    /// OSC 0/2 window title.
+    Title(String),
+    /// BEL (0x07).
+    Bell,
+    /// OSC 52 with a payload: put `data` on the clipboard.
+    ClipboardStore { clipboard: Clipboard, data: Vec<u8> },
+    /// OSC 52 with `?`: the app should answer with [`clipboard_reply`].
+    ClipboardLoad { clipboard: Clipboard },
+    /// OSC 7 working directory (already stripped of `file://host`).
+    Cwd(String),
+    /// Bytes that must be written back to the PTY (DSR/DA replies etc).
+    PtyWrite(Vec<u8>),
+    /// OSC 133 prompt/command boundary.
+    PromptMark(PromptMark),
+    /// XTWINOPS request that passed the window-ops policy.
+    WindowOp(WindowOp),
+    /// OSC 9;4 progress update.
+    Progress(Progress),
+}
+
+/// Receives [`EmuEvent`]s as the emulator parses output.
+pub trait EventListener: Send {
+    fn on_event(&mut self, event: EmuEvent);
+}
+
+/// Listener that drops everything; the default for a fresh `Emu`.
+pub struct NoopListener;
+
+impl EventListener for NoopListener {
+    fn on_event(&mut self, _event: EmuEvent) {}
+}
+
+impl EventListener for Sender<EmuEvent> {
+    fn on_event(&mut self, event: EmuEvent) {
+        let _ = self.send(event);
+    }
+}
+
+/// Encode an OSC 52 answer for a [`EmuEvent::ClipboardLoad`] request.
+pub fn clipboard_reply(clipboard: Clipboard, data: &[u8]) -> Vec<u8> {
+    use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+    let sel = match clipboard {
+        Clipboard::Clipboard => 'c',
+        Clipboard::Selection => 'p',
+    };
+    format!("\x1b]52;{sel};{}\x1b\\", B64.encode(data)).into_bytes()
+}
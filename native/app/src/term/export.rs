@@ -0,0 +1,452 @@
+//! Render the screen, a selection or the whole history as plain text,
+//! ANSI-escaped text, standalone HTML or SVG. HTML and SVG use the colors of
+//! the palette they are given, so exports match what was on screen. Lines
+//! can be prefixed with the time they arrived.
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{bail, Error};
+
+use super::emu::{Cell, Color, Emu, Flags, Rgba, Style, Underline};
+use crate::theme::Palette;
+
+/// Cell size used to lay out SVG exports, in user units.
+const SVG_CELL_W: f32 = 8.4;
+const SVG_CELL_H: f32 = 17.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Ansi,
+    Html,
+    Svg,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::Ansi => "ans",
+            Format::Html => "html",
+            Format::Svg => "svg",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "text" | "txt" => Format::Text,
+            "ansi" => Format::Ansi,
+            "html" => Format::Html,
+            "svg" => Format::Svg,
+            _ => bail!("unknown export format {s:?} (text, ansi, html, svg)"),
+        })
+    }
+}
+
+/// A span of history in absolute coordinates: line 0 is the oldest
+/// scrollback row and the live screen follows the scrollback. Both ends are
+/// inclusive `(line, col)` positions and may be given in either order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    /// The viewport as currently displayed (honours scrollback position).
+    Screen,
+    /// Scrollback plus the live screen.
+    History,
+    Selection(Selection),
+}
+
+impl FromStr for Region {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "screen" => Region::Screen,
+            "history" => Region::History,
+            _ => bail!("unknown export region {s:?} (screen, history)"),
+        })
+    }
+}
+
+/// Export settings beyond the region and format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Prefix every line with the time it arrived, see [`format_time`].
+    pub times: bool,
+}
+
+/// A line arrival time as RFC 3339 UTC with milliseconds, or blanks of the
+/// same width when it is unknown.
+pub fn format_time(ms: u64) -> String {
+    if ms == 0 {
+        return " ".repeat(24);
+    }
+    humantime::format_rfc3339_millis(UNIX_EPOCH + Duration::from_millis(ms)).to_string()
+}
+
+/// A run of cells with the same attributes.
+struct Span {
+    style: Style,
+    text: String,
+    /// Width in cells.
+    width: usize,
+}
+
+/// Export `region` of `emu` as `format`. Trailing blank cells are dropped
+/// from every line.
+pub fn export(emu: &Emu, region: Region, format: Format, palette: &Palette) -> String {
+    export_with(emu, region, format, palette, Options::default())
+}
+
+/// [`export`] with `options`. Times are written as a dim prefix, so they
+/// show up in every format.
+pub fn export_with(
+    emu: &Emu,
+    region: Region,
+    format: Format,
+    palette: &Palette,
+    options: Options,
+) -> String {
+    let stamp = Style {
+        flags: Flags::DIM,
+        ..Style::default()
+    };
+    let lines: Vec<Vec<Span>> = collect_lines(emu, region)
+        .into_iter()
+        .map(|(time, cells)| {
+            let mut line = spans(emu, trim_end(&cells));
+            if options.times {
+                let text = format_time(time) + " ";
+                let width = text.len();
+                line.insert(
+                    0,
+                    Span {
+                        style: stamp,
+                        text,
+                        width,
+                    },
+                );
+            }
+            line
+        })
+        .collect();
+    let cols = emu.cols + if options.times { 25 } else { 0 };
+    match format {
+        Format::Text => to_text(&lines),
+        Format::Ansi => to_ansi(&lines),
+        Format::Html => to_html(&lines, palette),
+        Format::Svg => to_svg(&lines, cols, palette),
+    }
+}
+
+/// The lines of `region` with their arrival times.
+fn collect_lines(emu: &Emu, region: Region) -> Vec<(u64, Cow<'_, [Cell]>)> {
+    let total = emu.scrollback.len() + emu.rows;
+    match region {
+        Region::Screen => (0..emu.rows)
+            .map(|y| (emu.visible_line_time(y), emu.visible_row(y)))
+            .collect(),
+        Region::History => (0..total)
+            .map(|l| (emu.history_line_time(l), emu.history_line(l)))
+            .collect(),
+        Region::Selection(sel) => {
+            let (start, end) = if sel.start <= sel.end {
+                (sel.start, sel.end)
+            } else {
+                (sel.end, sel.start)
+            };
+            if start.0 >= total {
+                return Vec::new();
+            }
+            let last = end.0.min(total - 1);
+            (start.0..=last)
+                .map(|l| {
+                    let row = emu.history_line(l);
+                    let from = if l == start.0 {
+                        start.1.min(row.len())
+                    } else {
+                        0
+                    };
+                    let to = if l == end.0 {
+                        (end.1 + 1).min(row.len())
+                    } else {
+                        row.len()
+                    };
+                    let cells = match row {
+                        Cow::Borrowed(row) => Cow::Borrowed(&row[from..to.max(from)]),
+                        Cow::Owned(row) => Cow::Owned(row[from..to.max(from)].to_vec()),
+                    };
+                    (emu.history_line_time(l), cells)
+                })
+                .collect()
+        }
+    }
+}
+
+fn trim_end(cells: &[Cell]) -> &[Cell] {
+    let len = cells
+        .iter()
+        .rposition(|c| *c != Cell::BLANK)
+        .map_or(0, |i| i + 1);
+    &cells[..len]
+}
+
+/// Group cells into attribute runs, skipping the spacer cell that follows
+/// each wide character.
+fn spans(emu: &Emu, cells: &[Cell]) -> Vec<Span> {
+    let mut out: Vec<Span> = Vec::new();
+    let mut x = 0;
+    while x < cells.len() {
+        let cell = cells[x];
+        let w = emu.char_width(cell.ch);
+        let style = *emu.style(cell.style);
+        match out.last_mut() {
+            Some(span) if span.style == style => {
+                span.text.push(cell.ch);
+                span.width += w;
+            }
+            _ => out.push(Span {
+                style,
+                text: cell.ch.to_string(),
+                width: w,
+            }),
+        }
+        x += w;
+    }
+    out
+}
+
+fn to_text(lines: &[Vec<Span>]) -> String {
+    let mut out = String::new();
+    for line in lines {
+        for span in line {
+            out.push_str(&span.text);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn to_ansi(lines: &[Vec<Span>]) -> String {
+    let mut out = String::new();
+    for line in lines {
+        let mut styled = false;
+        for span in line {
+            let sgr = sgr(&span.style);
+            if sgr.is_empty() {
+                if styled {
+                    out.push_str("\x1b[0m");
+                    styled = false;
+                }
+            } else {
+                let _ = write!(out, "\x1b[0;{sgr}m");
+                styled = true;
+            }
+            out.push_str(&span.text);
+        }
+        if styled {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// SGR parameters that recreate `style` from a reset state.
+fn sgr(style: &Style) -> String {
+    let mut p: Vec<String> = Vec::new();
+    for (flag, code) in [
+        (Flags::BOLD, "1"),
+        (Flags::DIM, "2"),
+        (Flags::ITALIC, "3"),
+        (Flags::BLINK, "5"),
+        (Flags::INVERSE, "7"),
+        (Flags::HIDDEN, "8"),
+        (Flags::STRIKE, "9"),
+    ] {
+        if style.flags.contains(flag) {
+            p.push(code.into());
+        }
+    }
+    match style.underline {
+        Underline::None => {}
+        Underline::Single => p.push("4".into()),
+        Underline::Double => p.push("4:2".into()),
+        Underline::Curly => p.push("4:3".into()),
+        Underline::Dotted => p.push("4:4".into()),
+        Underline::Dashed => p.push("4:5".into()),
+    }
+    push_color(&mut p, style.fg, 30, 90, "38");
+    push_color(&mut p, style.bg, 40, 100, "48");
+    match style.ul {
+        Color::Default => {}
+        Color::Indexed(i) => p.push(format!("58:5:{i}")),
+        Color::Rgb(r, g, b) => p.push(format!("58:2::{r}:{g}:{b}")),
+    }
+    p.join(";")
+}
+
+fn push_color(p: &mut Vec<String>, color: Color, base: u8, bright: u8, ext: &str) {
+    match color {
+        Color::Default => {}
+        Color::Indexed(i) if i < 8 => p.push((base + i).to_string()),
+        Color::Indexed(i) if i < 16 => p.push((bright + i - 8).to_string()),
+        Color::Indexed(i) => p.push(format!("{ext};5;{i}")),
+        Color::Rgb(r, g, b) => p.push(format!("{ext};2;{r};{g};{b}")),
+    }
+}
+
+fn hex(c: Rgba) -> String {
+    format!("#{:02x}{:02x}{:02x}", c.0, c.1, c.2)
+}
+
+fn escape_xml(s: &str, out: &mut String) {
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+/// CSS for the parts of a span that differ from the page defaults.
+fn css(style: &Style, palette: &Palette) -> String {
+    let (fg, bg) = palette.cell_colors(style);
+    let mut css = String::new();
+    if fg != palette.foreground {
+        let _ = write!(css, "color:{};", hex(fg));
+    }
+    if bg != palette.background {
+        let _ = write!(css, "background:{};", hex(bg));
+    }
+    if style.flags.contains(Flags::BOLD) {
+        css.push_str("font-weight:bold;");
+    }
+    if style.flags.contains(Flags::ITALIC) {
+        css.push_str("font-style:italic;");
+    }
+    let mut deco = Vec::new();
+    if style.underline != Underline::None {
+        deco.push("underline");
+    }
+    if style.flags.contains(Flags::STRIKE) {
+        deco.push("line-through");
+    }
+    if !deco.is_empty() {
+        let _ = write!(css, "text-decoration:{};", deco.join(" "));
+        let line = match style.underline {
+            Underline::Double => "double",
+            Underline::Curly => "wavy",
+            Underline::Dotted => "dotted",
+            Underline::Dashed => "dashed",
+            _ => "solid",
+        };
+        let _ = write!(css, "text-decoration-style:{line};");
+        if style.ul != Color::Default {
+            let ul = palette.underline_color(style, fg);
+            let _ = write!(css, "text-decoration-color:{};", hex(ul));
+        }
+    }
+    css
+}
+
+fn to_html(lines: &[Vec<Span>], palette: &Palette) -> String {
+    let fg = hex(palette.foreground);
+    let bg = hex(palette.background);
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>terminal-ui export</title>\n</head>\n\
+         <body style=\"margin:0;background:{bg}\">\n\
+         <pre style=\"margin:0;padding:1em;color:{fg};background:{bg};\
+         font-family:ui-monospace,Menlo,Consolas,monospace\">"
+    );
+    for line in lines {
+        for span in line {
+            let css = css(&span.style, palette);
+            if css.is_empty() {
+                escape_xml(&span.text, &mut out);
+            } else {
+                let _ = write!(out, "<span style=\"{css}\">");
+                escape_xml(&span.text, &mut out);
+                out.push_str("</span>");
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
+
+fn to_svg(lines: &[Vec<Span>], cols: usize, palette: &Palette) -> String {
+    let width = cols as f32 * SVG_CELL_W;
+    let height = lines.len().max(1) as f32 * SVG_CELL_H;
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\" font-family=\"ui-monospace,Menlo,Consolas,monospace\" \
+         font-size=\"14\" xml:space=\"preserve\">"
+    );
+    let _ = writeln!(
+        out,
+        "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+        hex(palette.background)
+    );
+    for (y, line) in lines.iter().enumerate() {
+        let top = y as f32 * SVG_CELL_H;
+        let mut x = 0;
+        for span in line {
+            let (fg, bg) = palette.cell_colors(&span.style);
+            let left = x as f32 * SVG_CELL_W;
+            let w = span.width as f32 * SVG_CELL_W;
+            if bg != palette.background {
+                let _ = writeln!(
+                    out,
+                    "<rect x=\"{left}\" y=\"{top}\" width=\"{w}\" height=\"{SVG_CELL_H}\" fill=\"{}\"/>",
+                    hex(bg)
+                );
+            }
+            if !span.text.trim().is_empty() {
+                let mut attrs = format!("fill=\"{}\"", hex(fg));
+                if span.style.flags.contains(Flags::BOLD) {
+                    attrs.push_str(" font-weight=\"bold\"");
+                }
+                if span.style.flags.contains(Flags::ITALIC) {
+                    attrs.push_str(" font-style=\"italic\"");
+                }
+                if span.style.underline != Underline::None {
+                    attrs.push_str(" text-decoration=\"underline\"");
+                } else if span.style.flags.contains(Flags::STRIKE) {
+                    attrs.push_str(" text-decoration=\"line-through\"");
+                }
+                let baseline = top + SVG_CELL_H * 0.8;
+                let _ = write!(
+                    out,
+                    "<text x=\"{left}\" y=\"{baseline}\" textLength=\"{w}\" {attrs}>"
+                );
+                escape_xml(&span.text, &mut out);
+                out.push_str("</text>\n");
+            }
+            x += span.width;
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
@@ -0,0 +1,68 @@
+//! Text for the debug inspector overlay: the emulator state that decides
+//! how the next byte is handled, counters of sequences it ignored, and the
+//! recent sequence log with unhandled entries marked `!`. Meant to be
+//! pasted into a bug report as is.
+
+use super::dump::describe;
+use super::emu::Emu;
+
+/// Lines of the overlay, top to bottom.
+pub fn inspect(emu: &Emu) -> Vec<String> {
+    let mut out = Vec::new();
+    out.push(format!(
+        "cursor {},{}{} {} {}{}",
+        emu.cur_x,
+        emu.cur_y,
+        if emu.cur_x == emu.cols {
+            " wrap-pending"
+        } else {
+            ""
+        },
+        if emu.cursor_visible {
+            "visible"
+        } else {
+            "hidden"
+        },
+        format!("{:?}", emu.cursor_style.shape).to_lowercase(),
+        if emu.cursor_style.blinking {
+            " blink"
+        } else {
+            ""
+        },
+    ));
+    let modes: Vec<&str> = [
+        (emu.autowrap, "autowrap"),
+        (emu.origin_mode, "origin"),
+        (emu.insert_mode, "insert"),
+        (emu.synchronized, "sync"),
+        (emu.rect_extent, "rect-extent"),
+    ]
+    .into_iter()
+    .filter_map(|(on, name)| on.then_some(name))
+    .collect();
+    out.push(format!("modes {}", modes.join(" ")));
+    out.push(format!(
+        "margins {}..{} of {}x{}",
+        emu.scroll_top, emu.scroll_bottom, emu.cols, emu.rows
+    ));
+    let cs = &emu.charsets;
+    out.push(format!(
+        "charset G0={} G1={} G2={} G3={} GL=G{}",
+        cs.g[0], cs.g[1], cs.g[2], cs.g[3], cs.gl
+    ));
+    let pen = describe(emu.pen());
+    out.push(format!(
+        "pen {}",
+        if pen.is_empty() { "default" } else { &pen }
+    ));
+    let u = emu.unknown;
+    out.push(format!(
+        "unknown csi={} osc={} dcs={} esc={}",
+        u.csi, u.osc, u.dcs, u.esc
+    ));
+    for entry in emu.trace() {
+        let mark = if entry.handled { ' ' } else { '!' };
+        out.push(format!("{mark} {}", entry.text));
+    }
+    out
+}
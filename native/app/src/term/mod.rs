@@ -1,3 +1,11 @@
+pub mod cast;
+pub mod dump;
 pub mod emu;
+pub mod event;
+pub mod export;
+pub mod inspect;
+pub mod search;
+pub mod shared;
 
 pub use emu::Emu;
+pub use event::EmuEvent;
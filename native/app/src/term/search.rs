@@ -0,0 +1,85 @@
+//! Find text in the scrollback and on screen. Lines are numbered like
+//! [`Emu::history_line`]: scrollback first, oldest at 0, then the screen.
+//! Scrollback spilled to disk is paged in as the search walks over it.
+//! Searches can be narrowed to the lines that arrived in a time range.
+
+use std::ops::Range;
+
+use super::emu::{Cell, Emu};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub line: usize,
+    /// First cell of the match.
+    pub col: usize,
+    /// Width of the match in cells.
+    pub width: usize,
+}
+
+/// Every occurrence of `needle`, oldest line first. Matches do not span
+/// lines; an empty needle matches nothing.
+pub fn find(emu: &Emu, needle: &str) -> Vec<Match> {
+    find_in(emu, needle, 0..emu.scrollback.len() + emu.rows)
+}
+
+/// Like [`find`], restricted to `lines`.
+pub fn find_in(emu: &Emu, needle: &str, lines: Range<usize>) -> Vec<Match> {
+    let total = emu.scrollback.len() + emu.rows;
+    search(emu, needle, lines.start..lines.end.min(total))
+}
+
+/// Like [`find`], restricted to lines whose arrival time (milliseconds since
+/// the Unix epoch) falls in `times`.
+pub fn find_between(emu: &Emu, needle: &str, times: Range<u64>) -> Vec<Match> {
+    search(emu, needle, lines_between(emu, times).into_iter())
+}
+
+/// Lines that arrived within `times`. Rows never written to have no time
+/// and are left out.
+pub fn lines_between(emu: &Emu, times: Range<u64>) -> Vec<usize> {
+    (0..emu.scrollback.len() + emu.rows)
+        .filter(|&l| {
+            let t = emu.history_line_time(l);
+            t != 0 && times.contains(&t)
+        })
+        .collect()
+}
+
+fn search(emu: &Emu, needle: &str, lines: impl Iterator<Item = usize>) -> Vec<Match> {
+    let needle: Vec<char> = needle.chars().collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    for line in lines {
+        let row = emu.history_line(line);
+        let (chars, cols) = chars_and_cols(emu, &row);
+        for start in 0..chars.len().saturating_sub(needle.len() - 1) {
+            if chars[start..start + needle.len()] == needle[..] {
+                let end = start + needle.len();
+                let end_col = cols.get(end).copied().unwrap_or(row.len());
+                out.push(Match {
+                    line,
+                    col: cols[start],
+                    width: end_col - cols[start],
+                });
+            }
+        }
+    }
+    out
+}
+
+/// The characters of `row` with the column each starts at, skipping the
+/// spacer cell after wide characters.
+fn chars_and_cols(emu: &Emu, row: &[Cell]) -> (Vec<char>, Vec<usize>) {
+    let mut chars = Vec::with_capacity(row.len());
+    let mut cols = Vec::with_capacity(row.len());
+    let mut x = 0;
+    while x < row.len() {
+        let ch = row[x].ch;
+        chars.push(ch);
+        cols.push(x);
+        x += emu.char_width(ch);
+    }
+    (chars, cols)
+}
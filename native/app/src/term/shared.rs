@@ -0,0 +1,132 @@
+//! An [`Emu`] shared between the UI thread and a per-session parser thread.
+//!
+//! The parser thread owns the PTY output stream and feeds it to the emulator
+//! in time-boxed slices, so a flood (`yes`, `find /`) never holds the lock
+//! for longer than [`PARSE_SLICE`]. The UI takes priority: once it asks for
+//! the lock the parser finishes the chunk in hand and steps aside, so a
+//! frame waits at most for one chunk of parsing.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Receiver;
+
+use super::Emu;
+
+/// Longest the parser holds the lock before giving the UI a chance.
+pub const PARSE_SLICE: Duration = Duration::from_millis(4);
+
+/// Output is split into pieces of at most this many bytes, which bounds how
+/// long the UI can wait for the parser to notice it.
+const PARSE_CHUNK: usize = 4096;
+
+#[derive(Clone)]
+pub struct SharedEmu {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    emu: Mutex<Emu>,
+    /// One of `UI_IDLE`, `UI_WAITING`, `UI_HOLDING`.
+    ui: AtomicU8,
+}
+
+const UI_IDLE: u8 = 0;
+const UI_WAITING: u8 = 1;
+const UI_HOLDING: u8 = 2;
+
+impl SharedEmu {
+    pub fn new(emu: Emu) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                emu: Mutex::new(emu),
+                ui: AtomicU8::new(UI_IDLE),
+            }),
+        }
+    }
+
+    /// Lock for the UI: the parser yields at its next chunk boundary and
+    /// stays off until the guard is dropped.
+    pub fn lock(&self) -> UiGuard<'_> {
+        self.inner.ui.store(UI_WAITING, Ordering::Release);
+        let guard = self.inner.emu.lock().unwrap_or_else(|e| e.into_inner());
+        self.inner.ui.store(UI_HOLDING, Ordering::Release);
+        UiGuard {
+            guard,
+            ui: &self.inner.ui,
+        }
+    }
+
+    /// Parse everything arriving on `rx` until the sender goes away.
+    pub fn spawn_parser(&self, rx: Receiver<Vec<u8>>) -> JoinHandle<()> {
+        let inner = self.inner.clone();
+        thread::Builder::new()
+            .name("emu-parser".into())
+            .spawn(move || inner.parse_loop(rx))
+            .expect("spawn parser thread")
+    }
+}
+
+impl Inner {
+    fn parse_loop(&self, rx: Receiver<Vec<u8>>) {
+        let mut pending: Vec<u8> = Vec::new();
+        let mut offset = 0;
+        loop {
+            if offset == pending.len() {
+                match rx.recv() {
+                    Ok(bytes) => (pending, offset) = (bytes, 0),
+                    Err(_) => return,
+                }
+            }
+            // Don't snatch the lock back while the UI is queued for it; once
+            // it holds the lock, blocking below costs nothing.
+            while self.ui.load(Ordering::Acquire) == UI_WAITING {
+                thread::yield_now();
+            }
+            let mut emu = self.emu.lock().unwrap_or_else(|e| e.into_inner());
+            let start = Instant::now();
+            loop {
+                let end = (offset + PARSE_CHUNK).min(pending.len());
+                emu.on_bytes(&pending[offset..end]);
+                offset = end;
+                if self.ui.load(Ordering::Acquire) != UI_IDLE || start.elapsed() >= PARSE_SLICE {
+                    break;
+                }
+                if offset == pending.len() {
+                    match rx.try_recv() {
+                        Ok(bytes) => (pending, offset) = (bytes, 0),
+                        Err(_) => break,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The UI's hold on the emulator.
+pub struct UiGuard<'a> {
+    guard: MutexGuard<'a, Emu>,
+    ui: &'a AtomicU8,
+}
+
+impl Drop for UiGuard<'_> {
+    fn drop(&mut self) {
+        self.ui.store(UI_IDLE, Ordering::Release);
+    }
+}
+
+impl std::ops::Deref for UiGuard<'_> {
+    type Target = Emu;
+
+    fn deref(&self) -> &Emu {
+        &self.guard
+    }
+}
+
+impl std::ops::DerefMut for UiGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Emu {
+        &mut self.guard
+    }
+}
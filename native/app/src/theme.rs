@@ -8,6 +8,8 @@ use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+use crate::term::emu::{Color, Flags, Rgba, Style};
+
 const TRON_TOML: &str = include_str!("../assets/themes/tron.toml");
 const MONO_LIGHT_TOML: &str = include_str!("../assets/themes/mono-light.toml");
 
@@ -117,3 +119,100 @@ pub fn parse_color(s: &str) -> wgpu::Color {
         })
         .unwrap_or(wgpu::Color::BLACK)
 }
+
+fn parse_rgba(s: &str) -> Rgba {
+    let [r, g, b, a] = CssColor::from_str(s)
+        .map(|c| c.to_rgba8())
+        .unwrap_or([0, 0, 0, 255]);
+    Rgba(r, g, b, a)
+}
+
+/// The 256-color table for a theme: its 16 ANSI colors, the 6x6x6 cube and
+/// the 24-step gray ramp, plus the default foreground/background.
+#[derive(Clone)]
+pub struct Palette {
+    pub colors: [Rgba; 256],
+    pub foreground: Rgba,
+    pub background: Rgba,
+}
+
+impl Palette {
+    pub fn new(theme: &Theme) -> Self {
+        let t = &theme.terminal;
+        let mut colors = [Rgba::default(); 256];
+        let ansi = [
+            &t.black,
+            &t.red,
+            &t.green,
+            &t.yellow,
+            &t.blue,
+            &t.magenta,
+            &t.cyan,
+            &t.white,
+            &t.bright_black,
+            &t.bright_red,
+            &t.bright_green,
+            &t.bright_yellow,
+            &t.bright_blue,
+            &t.bright_magenta,
+            &t.bright_cyan,
+            &t.bright_white,
+        ];
+        for (slot, css) in colors.iter_mut().zip(ansi) {
+            *slot = parse_rgba(css);
+        }
+        let level = |v: usize| if v == 0 { 0 } else { (55 + v * 40) as u8 };
+        for i in 0..216 {
+            colors[16 + i] = Rgba(level(i / 36), level((i / 6) % 6), level(i % 6), 255);
+        }
+        for i in 0..24 {
+            let v = (8 + i * 10) as u8;
+            colors[232 + i] = Rgba(v, v, v, 255);
+        }
+        Self {
+            colors,
+            foreground: parse_rgba(&t.foreground),
+            background: parse_rgba(&t.background),
+        }
+    }
+
+    pub fn resolve(&self, color: Color, is_fg: bool) -> Rgba {
+        match color {
+            Color::Default if is_fg => self.foreground,
+            Color::Default => self.background,
+            Color::Indexed(i) => self.colors[i as usize],
+            Color::Rgb(r, g, b) => Rgba(r, g, b, 255),
+        }
+    }
+
+    /// Underline color: SGR 58 if set, otherwise the resolved foreground.
+    pub fn underline_color(&self, style: &Style, fg: Rgba) -> Rgba {
+        match style.ul {
+            Color::Default => fg,
+            c => self.resolve(c, true),
+        }
+    }
+
+    /// Final (foreground, background) for a cell style after bold-as-bright, dim,
+    /// inverse and hidden are applied.
+    pub fn cell_colors(&self, style: &Style) -> (Rgba, Rgba) {
+        let fg = match style.fg {
+            Color::Indexed(i) if i < 8 && style.flags.contains(Flags::BOLD) => {
+                Color::Indexed(i + 8)
+            }
+            c => c,
+        };
+        let mut fg = self.resolve(fg, true);
+        let mut bg = self.resolve(style.bg, false);
+        if style.flags.contains(Flags::DIM) {
+            fg = Rgba(fg.0 / 3 * 2, fg.1 / 3 * 2, fg.2 / 3 * 2, fg.3);
+        }
+        if style.flags.contains(Flags::INVERSE) {
+            std::mem::swap(&mut fg, &mut bg);
+        }
+        if style.flags.contains(Flags::HIDDEN) {
+            fg = bg;
+        }
+        (fg, bg)
+    }
+}
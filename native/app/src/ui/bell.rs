@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+/// The same alarm the Electron build plays.
+const ALARM_WAV: &[u8] = include_bytes!("../../../../src/assets/audio/alarm.wav");
+
+/// What the app should do in response to one accepted bell.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ring {
+    pub sound: bool,
+    pub urgent: bool,
+}
+
+/// Turns BEL bytes into user-visible feedback. Rings that arrive faster than
+/// `min_interval` are dropped so a flood of 0x07 (e.g. `cat /dev/urandom`)
+/// produces a single flash instead of a strobe.
+pub struct Bell {
+    pub muted: bool,
+    visual: bool,
+    sound: bool,
+    urgent: bool,
+    min_interval: Duration,
+    flash_duration: Duration,
+    last_ring: Option<Instant>,
+}
+
+impl Bell {
+    pub fn new(cfg: &config::Bell) -> Self {
+        Self {
+            muted: false,
+            visual: cfg.visual,
+            sound: cfg.sound,
+            urgent: cfg.urgent,
+            min_interval: Duration::from_millis(cfg.min_interval_ms),
+            flash_duration: Duration::from_millis(150),
+            last_ring: None,
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    /// Register a bell. Returns `None` if it was rate limited.
+    pub fn ring(&mut self, focused: bool) -> Option<Ring> {
+        let now = Instant::now();
+        if let Some(last) = self.last_ring {
+            if now.duration_since(last) < self.min_interval {
+                return None;
+            }
+        }
+        self.last_ring = Some(now);
+        Some(Ring {
+            sound: self.sound && !self.muted,
+            urgent: self.urgent && !focused,
+        })
+    }
+
+    /// Opacity of the visual bell overlay, fading from 1.0 to 0.0.
+    pub fn flash_alpha(&self) -> f32 {
+        if !self.visual {
+            return 0.0;
+        }
+        let Some(last) = self.last_ring else {
+            return 0.0;
+        };
+        let t = last.elapsed().as_secs_f32() / self.flash_duration.as_secs_f32();
+        (1.0 - t).clamp(0.0, 1.0)
+    }
+}
+
+/// Play the alarm sound with whatever system player is available. Runs
+/// detached; failures are logged and otherwise ignored. Rings while a sound
+/// is still playing are skipped, so at most one player runs at a time.
+pub fn play_alarm() {
+    static PLAYING: AtomicBool = AtomicBool::new(false);
+    if PLAYING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    std::thread::spawn(|| {
+        if let Some(path) = alarm_path() {
+            let played = players(&path).into_iter().any(|mut cmd| {
+                let status = cmd.stdout(Stdio::null()).stderr(Stdio::null()).status();
+                matches!(status, Ok(s) if s.success())
+            });
+            if !played {
+                log::debug!("bell: no audio player available");
+            }
+        }
+        PLAYING.store(false, Ordering::Release);
+    });
+}
+
+/// Commands that play `path`, in order of preference.
+fn players(path: &Path) -> Vec<Command> {
+    let with = |prog: &str, args: &[&str]| {
+        let mut cmd = Command::new(prog);
+        cmd.args(args).arg(path);
+        cmd
+    };
+    if cfg!(target_os = "macos") {
+        vec![with("afplay", &[])]
+    } else if cfg!(windows) {
+        // Trailing arguments to -Command become part of the script, not
+        // `$args`, so the path goes into the script as a quoted literal.
+        let quoted = path.display().to_string().replace('\'', "''");
+        let mut cmd = Command::new("powershell.exe");
+        cmd.args([
+            "-NoProfile",
+            "-Command",
+            &format!("(New-Object Media.SoundPlayer '{quoted}').PlaySync()"),
+        ]);
+        vec![cmd]
+    } else {
+        vec![with("paplay", &[]), with("aplay", &["-q"])]
+    }
+}
+
+/// The wav is embedded in the binary and written to the cache dir on first use.
+fn alarm_path() -> Option<PathBuf> {
+    let dir = dirs::cache_dir()?.join("terminal-ui");
+    let path = dir.join("alarm.wav");
+    if !path.exists() {
+        if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, ALARM_WAV))
+        {
+            log::warn!("bell: cannot write {}: {e}", path.display());
+            return None;
+        }
+    }
+    Some(path)
+}
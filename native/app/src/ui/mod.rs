@@ -1,2 +1,3 @@
+pub mod bell;
 pub mod panels;
 pub mod theme_switcher;
@@ -0,0 +1,104 @@
+use app::gfx::bidi::layout;
+use app::term::emu::Emu;
+
+/// Row 0 as drawn: glyphs in visual order, trailing blanks trimmed.
+fn drawn(emu: &Emu) -> String {
+    let row = emu.visible_row(0);
+    let Some(layout) = layout(emu, &row) else {
+        return row
+            .iter()
+            .map(|c| c.ch)
+            .collect::<String>()
+            .trim_end()
+            .into();
+    };
+    (0..row.len())
+        .map(|v| layout.glyph(layout.logical_col(v)))
+        .collect::<String>()
+        .trim_end()
+        .into()
+}
+
+fn emu_with(text: &str) -> Emu {
+    let mut emu = Emu::new(20, 1);
+    emu.on_bytes(text.as_bytes());
+    emu
+}
+
+#[test]
+fn left_to_right_rows_have_no_layout() {
+    let emu = emu_with("plain ascii (1+2)");
+    assert!(layout(&emu, &emu.visible_row(0)).is_none());
+}
+
+#[test]
+fn hebrew_runs_are_reversed_in_place() {
+    let emu = emu_with("ls שלום.txt ok");
+    // The dot sits between Hebrew and Latin, so it stays with the line.
+    assert_eq!(drawn(&emu), "ls םולש.txt ok");
+    // Storage is untouched.
+    assert_eq!(emu.cell(3, 0).ch, 'ש');
+}
+
+#[test]
+fn numbers_keep_their_order_inside_rtl_text() {
+    let emu = emu_with("שלום 123 עולם");
+    assert_eq!(drawn(&emu), "םלוע 123 םולש");
+}
+
+#[test]
+fn brackets_are_mirrored_in_rtl_runs() {
+    let emu = emu_with("א(ב)ג");
+    assert_eq!(drawn(&emu), "ג(ב)א");
+}
+
+#[test]
+fn arabic_letters_are_joined() {
+    // بيت: beh initial, yeh medial, teh final; drawn right to left.
+    let emu = emu_with("\u{628}\u{64a}\u{62a}");
+    assert_eq!(drawn(&emu), "\u{fe96}\u{fef4}\u{fe91}");
+    // دار: dal does not join forward, so alef stands alone after it.
+    let emu = emu_with("\u{62f}\u{627}\u{631}");
+    assert_eq!(drawn(&emu), "\u{fead}\u{fe8d}\u{fea9}");
+}
+
+#[test]
+fn lam_alef_is_drawn_as_one_ligature() {
+    // لا on its own: the isolated ligature, and the alef's cell is blank.
+    let emu = emu_with("\u{644}\u{627}");
+    assert_eq!(drawn(&emu), " \u{fefb}");
+    // سلام: seen joins the ligature, which takes its final form.
+    let emu = emu_with("\u{633}\u{644}\u{627}\u{645}");
+    assert_eq!(drawn(&emu), "\u{fee1} \u{fefc}\u{feb3}");
+    // لأ with a fatha between the letters still ligates.
+    let emu = emu_with("\u{644}\u{64e}\u{623}");
+    assert_eq!(emu.cell(0, 0).ch, '\u{644}');
+    let layout = layout(&emu, &emu.visible_row(0)).unwrap();
+    assert_eq!(layout.glyph(0), '\u{fef7}');
+}
+
+#[test]
+fn cursor_and_selection_map_between_logical_and_visual() {
+    let emu = emu_with("ab אבג cd");
+    let l = layout(&emu, &emu.visible_row(0)).unwrap();
+    // "ab " then the Hebrew run drawn reversed over columns 3..6.
+    assert_eq!((l.visual_col(3), l.visual_col(5)), (5, 3));
+    assert_eq!(l.logical_col(3), 5);
+    assert_eq!(l.visual_col(7), 7);
+    for x in 0..20 {
+        assert_eq!(l.logical_col(l.visual_col(x)), x);
+    }
+    // Dragging over the last two drawn Hebrew letters and the space after.
+    assert_eq!(l.selected_cols(4..7), vec![3, 4, 6]);
+}
+
+#[test]
+fn multi_cell_characters_move_as_one() {
+    // A VS16-widened heart between Hebrew letters is part of the RTL run.
+    let emu = emu_with("\u{5d0}\u{2764}\u{fe0f}\u{5d1}");
+    let l = layout(&emu, &emu.visible_row(0)).unwrap();
+    assert_eq!(
+        (0..4).map(|x| l.visual_col(x)).collect::<Vec<_>>(),
+        vec![3, 1, 2, 0]
+    );
+}
@@ -0,0 +1,123 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use app::term::cast::{Cast, EventData, Player, Recorder};
+use app::term::dump::dump;
+use app::term::Emu;
+
+fn row_text(emu: &Emu, y: usize) -> String {
+    (0..emu.cols).map(|x| emu.cell(x, y).ch).collect()
+}
+
+#[test]
+fn recorder_writes_asciicast_v2() {
+    let path = std::env::temp_dir().join(format!("cast-test-{}.cast", std::process::id()));
+    let mut rec = Recorder::create(&path, 20, 5, "terminal-ui", true).unwrap();
+    let crab = "🦀".as_bytes();
+    // A multi-byte char split across two reads must not be mangled.
+    rec.output(&[b"hi ", &crab[..2]].concat()).unwrap();
+    rec.output(&crab[2..]).unwrap();
+    rec.output(b"\xff!").unwrap();
+    rec.input(b"ls\r").unwrap();
+    rec.resize(30, 6).unwrap();
+    rec.finish().unwrap();
+
+    let cast = Cast::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        (cast.header.version, cast.header.width, cast.header.height),
+        (2, 20, 5)
+    );
+    assert_eq!(cast.header.env["TERM"], "terminal-ui");
+    let data: Vec<_> = cast.events.iter().map(|e| e.data.clone()).collect();
+    assert_eq!(
+        data,
+        vec![
+            EventData::Output("hi ".into()),
+            EventData::Output("🦀".into()),
+            EventData::Output("\u{fffd}!".into()),
+            EventData::Input("ls\r".into()),
+            EventData::Resize { cols: 30, rows: 6 },
+        ]
+    );
+    assert!(cast.events.windows(2).all(|w| w[0].time <= w[1].time));
+}
+
+const SAMPLE: &str = r#"{"version": 2, "width": 10, "height": 2}
+[0.5, "o", "one "]
+[1.0, "m", "marker"]
+[2.0, "o", "two "]
+[3.0, "r", "12x3"]
+[4.0, "o", "three"]
+"#;
+
+#[test]
+fn player_follows_clock_speed_and_pause() {
+    let cast = Cast::parse(Cursor::new(SAMPLE)).unwrap();
+    assert_eq!(cast.events.len(), 4, "marker events are skipped");
+    let mut emu = Emu::new(10, 2);
+    let mut player = Player::new(cast, 2.0);
+
+    player.advance(Duration::from_millis(500), &mut emu);
+    assert_eq!(row_text(&emu, 0), "one       ");
+    player.toggle_pause();
+    player.advance(Duration::from_secs(10), &mut emu);
+    assert_eq!(player.position(), 1.0);
+    player.toggle_pause();
+    player.advance(Duration::from_millis(600), &mut emu);
+    assert_eq!(row_text(&emu, 0), "one two   ");
+    player.advance(Duration::from_secs(1), &mut emu);
+    assert_eq!((emu.cols, emu.rows), (12, 3));
+    assert_eq!(row_text(&emu, 0), "three       ");
+    assert!(player.finished());
+}
+
+#[test]
+fn player_seeks_backwards_from_a_clean_screen() {
+    let cast = Cast::parse(Cursor::new(SAMPLE)).unwrap();
+    let mut emu = Emu::new(10, 2);
+    let mut player = Player::new(cast, 1.0);
+    player.seek(10.0, &mut emu);
+    assert_eq!(player.position(), 4.0);
+    player.seek(2.5, &mut emu);
+    assert_eq!((emu.cols, emu.rows), (10, 2));
+    assert_eq!(row_text(&emu, 0), "one two   ");
+    assert!(!player.finished());
+}
+
+#[test]
+fn rejects_other_versions() {
+    let v1 = "{\"version\": 1, \"width\": 80, \"height\": 24}\n";
+    assert!(Cast::parse(Cursor::new(v1)).is_err());
+}
+
+#[test]
+fn seeking_resumes_from_checkpoints() {
+    // Enough output for several checkpoints, with styles, a resize and
+    // scrollback along the way.
+    let mut text = String::from("{\"version\": 2, \"width\": 40, \"height\": 5}\n");
+    for i in 0..60 {
+        let line = format!("\u{1b}[3{}mline {i} {}\u{1b}[m\r\n", i % 8, "x".repeat(30));
+        let chunk = serde_json::to_string(&line.repeat(1000)).unwrap();
+        text.push_str(&format!("[{i}.0, \"o\", {chunk}]\n"));
+        if i == 30 {
+            text.push_str("[30.5, \"r\", \"50x6\"]\n");
+        }
+    }
+    let cast = Cast::parse(Cursor::new(text)).unwrap();
+    let at = |time: f64| {
+        let mut emu = Emu::new(40, 5);
+        let mut player = Player::new(cast.clone(), 1.0);
+        player.seek(time, &mut emu);
+        dump(&emu)
+    };
+
+    let mut emu = Emu::new(40, 5);
+    let mut player = Player::new(cast.clone(), 1.0);
+    player.seek(59.0, &mut emu);
+    for time in [45.5, 12.0, 31.0, 0.0, 58.0] {
+        player.seek(time, &mut emu);
+        assert_eq!(player.position(), time);
+        assert_eq!(dump(&emu), at(time), "after seeking to {time}");
+    }
+}
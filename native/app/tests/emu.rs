@@ -0,0 +1,489 @@
+use app::term::dump::dump;
+use app::term::emu::{
+    Color, CursorShape, CursorStyle, Emu, Flags, LineAttr, Snapshot, Style, Underline, WidthPolicy,
+};
+use app::term::event::{Clipboard, EmuEvent, ProgressState, PromptMark, WindowOp};
+use app::theme::{load_theme, Palette};
+use crossbeam_channel::{unbounded, Receiver};
+
+fn with_events(cols: usize, rows: usize) -> (Emu, Receiver<EmuEvent>) {
+    let (tx, rx) = unbounded();
+    let mut emu = Emu::new(cols, rows);
+    emu.set_listener(Box::new(tx));
+    (emu, rx)
+}
+
+fn row_text(emu: &Emu, y: usize) -> String {
+    (0..emu.cols).map(|x| emu.cell(x, y).ch).collect()
+}
+
+/// Scrollback with style ids resolved, comparable across emulators.
+fn styled_scrollback(emu: &Emu) -> Vec<Vec<(char, Style)>> {
+    emu.scrollback
+        .iter()
+        .map(|row| row.iter().map(|c| (c.ch, *emu.style(c.style))).collect())
+        .collect()
+}
+
+#[test]
+fn decscusr_sets_and_resets_cursor_style() {
+    let mut emu = Emu::new(10, 3);
+    emu.set_default_cursor_style(CursorStyle {
+        shape: CursorShape::Underline,
+        blinking: false,
+    });
+    emu.on_bytes(b"\x1b[5 q");
+    assert_eq!(emu.cursor_style.shape, CursorShape::Bar);
+    assert!(emu.cursor_style.blinking);
+    emu.on_bytes(b"\x1b[0 q");
+    assert_eq!(emu.cursor_style.shape, CursorShape::Underline);
+    assert!(!emu.cursor_style.blinking);
+}
+
+#[test]
+fn dectcem_hides_and_shows_cursor() {
+    let mut emu = Emu::new(10, 3);
+    emu.on_bytes(b"\x1b[?25l");
+    assert!(!emu.cursor_visible);
+    emu.on_bytes(b"\x1b[?25h");
+    assert!(emu.cursor_visible);
+}
+
+#[test]
+fn bel_burst_is_coalesced() {
+    let (mut emu, rx) = with_events(10, 3);
+    emu.on_bytes(b"ding\x07\x07");
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![EmuEvent::Bell]);
+}
+
+#[test]
+fn osc_and_reports_are_emitted() {
+    let (mut emu, rx) = with_events(10, 3);
+    emu.on_bytes(b"\x1b]2;hello;world\x07");
+    emu.on_bytes(b"\x1b]7;file://box/tmp/a%20b\x1b\\");
+    emu.on_bytes(b"\x1b]52;c;aGk=\x07\x1b]133;D;1\x07");
+    emu.on_bytes(b"ab\x1b[6n");
+    let events: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        events,
+        vec![
+            EmuEvent::Title("hello;world".into()),
+            EmuEvent::Cwd("/tmp/a b".into()),
+            EmuEvent::ClipboardStore {
+                clipboard: Clipboard::Clipboard,
+                data: b"hi".to_vec(),
+            },
+            EmuEvent::PromptMark(PromptMark::CommandEnd { exit_code: Some(1) }),
+            EmuEvent::PtyWrite(b"\x1b[1;3R".to_vec()),
+        ]
+    );
+    assert_eq!(emu.title, "hello;world");
+}
+
+#[test]
+fn decaln_and_ris() {
+    let mut emu = Emu::new(4, 2);
+    emu.on_bytes(b"\x1b[2;3r\x1b#8");
+    assert_eq!(row_text(&emu, 0), "EEEE");
+    assert_eq!((emu.scroll_top, emu.scroll_bottom), (0, 1));
+    emu.on_bytes(b"\x1b[?25l\x1b[4hx\x1bc");
+    assert_eq!(row_text(&emu, 0), "    ");
+    assert!(emu.cursor_visible && !emu.insert_mode);
+    assert_eq!((emu.cur_x, emu.cur_y), (0, 0));
+}
+
+#[test]
+fn decstr_keeps_screen() {
+    let mut emu = Emu::new(4, 3);
+    emu.on_bytes(b"ab\x1b[2;3r\x1b[?6h\x1b[!p");
+    assert_eq!(row_text(&emu, 0), "ab  ");
+    assert!(!emu.origin_mode);
+    assert_eq!((emu.scroll_top, emu.scroll_bottom), (0, 2));
+}
+
+#[test]
+fn double_width_line_wraps_at_half_width() {
+    let mut emu = Emu::new(6, 2);
+    emu.on_bytes(b"\x1b#6abcd");
+    assert_eq!(emu.line_attrs[0], LineAttr::DoubleWidth);
+    assert_eq!(row_text(&emu, 0), "abc   ");
+    assert_eq!(row_text(&emu, 1), "d     ");
+}
+
+#[test]
+fn sgr_colors_are_stored_unresolved() {
+    let mut emu = Emu::new(8, 1);
+    emu.on_bytes(b"\x1b[1;31ma\x1b[38;5;200;48:2::1:2:3mb\x1b[39;22mc");
+    let (a, b, c) = (
+        *emu.cell_style(0, 0),
+        *emu.cell_style(1, 0),
+        *emu.cell_style(2, 0),
+    );
+    assert_eq!(a.fg, Color::Indexed(1));
+    assert!(a.flags.contains(Flags::BOLD));
+    assert_eq!((b.fg, b.bg), (Color::Indexed(200), Color::Rgb(1, 2, 3)));
+    assert_eq!((c.fg, c.bg), (Color::Default, Color::Rgb(1, 2, 3)));
+    assert!(!c.flags.contains(Flags::BOLD));
+}
+
+#[test]
+fn theme_switch_recolors_existing_cells() {
+    let mut emu = Emu::new(8, 1);
+    emu.on_bytes(b"x\x1b[32my");
+    let tron = Palette::new(&load_theme("tron").unwrap());
+    let light = Palette::new(&load_theme("mono-light").unwrap());
+    assert_eq!(tron.cell_colors(emu.cell_style(0, 0)).0, tron.foreground);
+    assert_eq!(light.cell_colors(emu.cell_style(0, 0)).0, light.foreground);
+    assert_eq!(light.cell_colors(emu.cell_style(1, 0)).0, light.colors[2]);
+}
+
+#[test]
+fn scrolled_rows_land_in_scrollback() {
+    let mut emu = Emu::new(3, 2);
+    emu.on_bytes(b"a\r\nb\r\nc");
+    assert_eq!(emu.scrollback.len(), 1);
+    emu.scroll_display(5);
+    assert_eq!(emu.display_offset, 1);
+    assert_eq!(emu.visible_row(0)[0].ch, 'a');
+    assert_eq!(emu.visible_row(1)[0].ch, 'b');
+}
+
+#[test]
+fn view_stays_on_its_text_while_full_scrollback_evicts() {
+    let mut emu = Emu::new(3, 2);
+    emu.scrollback_limit = 4;
+    emu.on_bytes(b"0\r\n1\r\n2\r\n3\r\n4\r\n5");
+    assert_eq!(emu.scrollback.len(), 4);
+    emu.scroll_display(2);
+    assert_eq!(emu.visible_row(0)[0].ch, '2');
+    emu.on_bytes(b"\r\n6");
+    assert_eq!(emu.display_offset, 3);
+    assert_eq!(emu.visible_row(0)[0].ch, '2');
+    // Once the row is evicted the view stops at the oldest one left.
+    emu.on_bytes(b"\r\n7\r\n8");
+    assert_eq!(emu.display_offset, 4);
+    assert_eq!(emu.visible_row(0)[0].ch, '3');
+}
+
+#[test]
+fn underline_styles_and_color() {
+    let mut emu = Emu::new(8, 1);
+    emu.on_bytes(b"\x1b[4:3;58:5:9ma\x1b[21mb\x1b[4:0;59mc\x1b[4md");
+    let cells: Vec<_> = (0..4).map(|x| *emu.cell_style(x, 0)).collect();
+    assert_eq!(cells[0].underline, Underline::Curly);
+    assert_eq!(cells[0].ul, Color::Indexed(9));
+    assert_eq!(cells[1].underline, Underline::Double);
+    assert_eq!(
+        (cells[2].underline, cells[2].ul),
+        (Underline::None, Color::Default)
+    );
+    assert_eq!(cells[3].underline, Underline::Single);
+}
+
+#[test]
+fn rectangle_fill_copy_and_erase() {
+    let mut emu = Emu::new(5, 3);
+    emu.on_bytes(b"\x1b[65;1;2;2;3$x");
+    assert_eq!(row_text(&emu, 0), " AA  ");
+    assert_eq!(row_text(&emu, 1), " AA  ");
+    emu.on_bytes(b"\x1b[1;2;2;3;1;2;4;1$v");
+    assert_eq!(row_text(&emu, 1), " AAAA");
+    assert_eq!(row_text(&emu, 2), "   AA");
+    emu.on_bytes(b"\x1b[2;1;3;5$z");
+    assert_eq!(row_text(&emu, 1), "     ");
+    assert_eq!(row_text(&emu, 0), " AA  ");
+}
+
+#[test]
+fn selective_erase_skips_protected_cells() {
+    let mut emu = Emu::new(4, 2);
+    emu.on_bytes(b"a\x1b[1\"qb\x1b[0\"qc\r\n\x1b[1\"qd");
+    emu.on_bytes(b"\x1b[?2J");
+    assert_eq!(row_text(&emu, 0), " b  ");
+    assert_eq!(row_text(&emu, 1), "d   ");
+    emu.on_bytes(b"\x1b[1;1;2;4${");
+    assert_eq!(row_text(&emu, 0), " b  ");
+    emu.on_bytes(b"\x1b[2J");
+    assert_eq!(row_text(&emu, 0), "    ");
+}
+
+#[test]
+fn deccara_honours_decsace() {
+    let mut emu = Emu::new(4, 2);
+    emu.on_bytes(b"\x1b[1;3;2;2;1$r");
+    assert!(emu.cell_style(3, 0).flags.contains(Flags::BOLD));
+    assert!(!emu.cell_style(2, 1).flags.contains(Flags::BOLD));
+    emu.on_bytes(b"\x1b[2*x\x1b[1;3;2;3;7$r");
+    assert!(emu.cell_style(2, 1).flags.contains(Flags::INVERSE));
+    assert!(!emu.cell_style(3, 0).flags.contains(Flags::INVERSE));
+}
+
+#[test]
+fn rectangle_columns_past_the_edge_are_clamped() {
+    let mut emu = Emu::new(5, 3);
+    emu.on_bytes(b"\x1b[2;10;3;20;1$r");
+    assert!(emu.cell_style(4, 1).flags.contains(Flags::BOLD));
+    assert!(emu.cell_style(0, 2).flags.contains(Flags::BOLD));
+    assert!(!emu.cell_style(3, 1).flags.contains(Flags::BOLD));
+    emu.on_bytes(b"\x1b[1;1000;2;3;1$t");
+    assert!(emu.cell_style(4, 0).flags.contains(Flags::BOLD));
+    assert!(emu.cell_style(2, 1).flags.contains(Flags::BOLD));
+    assert!(!emu.cell_style(3, 1).flags.contains(Flags::BOLD));
+    emu.on_bytes(b"\x1b[2*x\x1b[2;10;3;20;7$r\x1b[1;1000;2;3;7$t");
+    assert!(emu.cell_style(4, 2).flags.contains(Flags::INVERSE));
+    assert!(!emu.cell_style(4, 0).flags.contains(Flags::INVERSE));
+    emu.on_bytes(b"\x1b[65;1;9;1;20$x");
+    assert_eq!(row_text(&emu, 0), "    A");
+}
+
+#[test]
+fn xtwinops_reports_and_policy() {
+    let (mut emu, rx) = with_events(80, 24);
+    emu.set_cell_size(9, 18);
+    emu.on_bytes(b"\x1b[14t\x1b[16t\x1b[18t\x1b[2t");
+    let events: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        events,
+        vec![
+            EmuEvent::PtyWrite(b"\x1b[4;432;720t".to_vec()),
+            EmuEvent::PtyWrite(b"\x1b[6;18;9t".to_vec()),
+            EmuEvent::PtyWrite(b"\x1b[8;24;80t".to_vec()),
+        ]
+    );
+    emu.allow_window_ops = true;
+    emu.on_bytes(b"\x1b[8;30t");
+    assert_eq!(
+        rx.try_recv().unwrap(),
+        EmuEvent::WindowOp(WindowOp::ResizeCells {
+            rows: Some(30),
+            cols: None
+        })
+    );
+}
+
+#[test]
+fn xtwinops_pixel_size_saturates() {
+    let (mut emu, rx) = with_events(80, 24);
+    emu.set_cell_size(u32::MAX / 2, u32::MAX / 2);
+    emu.on_bytes(b"\x1b[14t");
+    let max = u32::MAX;
+    assert_eq!(
+        rx.try_recv().unwrap(),
+        EmuEvent::PtyWrite(format!("\x1b[4;{max};{max}t").into_bytes())
+    );
+}
+
+#[test]
+fn osc_9_4_progress() {
+    let (mut emu, rx) = with_events(10, 2);
+    emu.on_bytes(b"\x1b]9;4;1;42\x1b\\\x1b]9;4;4\x07\x1b]9;4;0\x07\x1b]9;hello\x07");
+    let states: Vec<_> = rx
+        .try_iter()
+        .map(|e| match e {
+            EmuEvent::Progress(p) => (p.state, p.percent),
+            other => panic!("unexpected {other:?}"),
+        })
+        .collect();
+    assert_eq!(
+        states,
+        vec![
+            (ProgressState::Normal, 42),
+            (ProgressState::Paused, 42),
+            (ProgressState::None, 0),
+        ]
+    );
+}
+
+#[test]
+fn synchronized_output_mode() {
+    let (mut emu, rx) = with_events(10, 2);
+    emu.on_bytes(b"\x1b[?2026h\x1b[?2026$p");
+    assert!(emu.synchronized);
+    emu.on_bytes(b"\x1b[?2026l\x1b[?2026$p\x1b[?9999$p");
+    assert!(!emu.synchronized);
+    let replies: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        replies,
+        vec![
+            EmuEvent::PtyWrite(b"\x1b[?2026;1$y".to_vec()),
+            EmuEvent::PtyWrite(b"\x1b[?2026;2$y".to_vec()),
+            EmuEvent::PtyWrite(b"\x1b[?9999;0$y".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn wide_char_on_one_column_screen_stays_in_bounds() {
+    let mut emu = Emu::new(1, 2);
+    emu.on_bytes("世界".as_bytes());
+    emu.check_invariants();
+    assert_eq!(emu.cell(0, 1).ch, '界');
+    emu.on_bytes(b"\x1b[?7l");
+    emu.on_bytes("世".as_bytes());
+    emu.check_invariants();
+}
+
+#[test]
+fn snapshot_round_trips_through_json() {
+    let mut emu = Emu::new(12, 3);
+    emu.on_bytes(b"\x1b]2;build\x07\x1b]7;file:///tmp\x07");
+    for i in 0..20 {
+        emu.on_bytes(format!("\x1b[3{}mline {i}\x1b[m\r\n", i % 8).as_bytes());
+    }
+    emu.on_bytes(b"\x1b[1;4:3mhi\x1b#6\x1b[?25l\x1b[5 q");
+    let snap = emu.snapshot(1 << 20).unwrap();
+    let json = snap.to_json().unwrap();
+
+    let mut restored = Emu::new(12, 3);
+    restored
+        .restore(&Snapshot::from_json(&json).unwrap())
+        .unwrap();
+    assert_eq!(dump(&restored), dump(&emu));
+    assert_eq!(styled_scrollback(&restored), styled_scrollback(&emu));
+    assert_eq!(restored.cwd.as_deref(), Some("/tmp"));
+    // The pen survives too: text printed after restore keeps bold + curly.
+    restored.on_bytes(b"!");
+    assert!(restored.cell_style(2, 2).flags.contains(Flags::BOLD));
+    assert_eq!(restored.cell_style(2, 2).underline, Underline::Curly);
+}
+
+#[test]
+fn snapshot_scrollback_respects_budget() {
+    let mut emu = Emu::new(40, 2);
+    // Unique-ish rows so compression cannot make the budget meaningless.
+    for i in 0..600u32 {
+        let noise = i.wrapping_mul(2654435761);
+        emu.on_bytes(format!("{i} {noise:x} {:x}\r\n", noise.rotate_left(7)).as_bytes());
+    }
+    let snap = emu.snapshot(2048).unwrap();
+    assert!(snap.scrollback.data.len() <= 2048);
+    assert!(snap.scrollback.rows > 0 && snap.scrollback.rows < 600);
+
+    let mut restored = Emu::new(40, 2);
+    restored.restore(&snap).unwrap();
+    assert_eq!(restored.scrollback.len(), snap.scrollback.rows);
+    assert_eq!(restored.scrollback.back(), emu.scrollback.back());
+}
+
+#[test]
+fn snapshot_fits_a_different_size_and_checks_version() {
+    let mut emu = Emu::new(10, 4);
+    emu.on_bytes(b"one\r\ntwo\r\nthree\r\nfour");
+    let snap = emu.snapshot(1 << 20).unwrap();
+
+    let mut small = Emu::new(3, 2);
+    small.restore(&snap).unwrap();
+    small.check_invariants();
+    assert_eq!(row_text(&small, 0), "thr");
+    assert_eq!(row_text(&small, 1), "fou");
+    assert_eq!((small.cur_x, small.cur_y), (3, 1));
+    assert_eq!(small.scrollback.len(), 2);
+
+    let json = snap
+        .to_json()
+        .unwrap()
+        .replacen("\"version\":1", "\"version\":99", 1);
+    assert!(Snapshot::from_json(&json).is_err());
+}
+
+#[test]
+fn cells_are_compact() {
+    assert_eq!(std::mem::size_of::<app::term::emu::Cell>(), 8);
+}
+
+#[test]
+fn unused_styles_are_compacted() {
+    let mut emu = Emu::new(4, 2);
+    emu.scrollback_limit = 1;
+    // Thousands of distinct colors, each soon scrolled out of existence.
+    for i in 0..5000u32 {
+        let (r, g) = (i % 256, i / 256);
+        emu.on_bytes(format!("\x1b[38;2;{r};{g};0mx\r\n").as_bytes());
+        emu.check_invariants();
+    }
+    emu.on_bytes(b"\x1b[38;2;1;2;3my");
+    assert_eq!(emu.cell_style(0, 1).fg, Color::Rgb(1, 2, 3));
+    assert_eq!(emu.cell_style(0, 0).fg, Color::Rgb(135, 19, 0));
+}
+
+#[test]
+fn region_scroll_keeps_rows_outside_the_margins() {
+    let mut emu = Emu::new(3, 4);
+    emu.on_bytes(b"top\r\na\r\nb\r\nbot\x1b[2;3r\x1b[3;1H\n\n");
+    assert_eq!(row_text(&emu, 0), "top");
+    assert_eq!(row_text(&emu, 1), "   ");
+    assert_eq!(row_text(&emu, 2), "   ");
+    assert_eq!(row_text(&emu, 3), "bot");
+    assert!(emu.scrollback.is_empty());
+    emu.on_bytes(b"\x1b[2;1Hx\x1bM\x1bM");
+    assert_eq!(row_text(&emu, 3), "bot");
+    assert_eq!(row_text(&emu, 2), "   ");
+    assert_eq!(row_text(&emu, 0), "top");
+    emu.check_invariants();
+}
+
+#[test]
+fn ascii_fast_path_respects_pending_sequences() {
+    let mut emu = Emu::new(20, 2);
+    // ST-terminated OSC, an ignored CSI, a C0 inside a CSI, a DCS and a
+    // CAN-aborted CSI: no parameter or terminator byte may be printed.
+    emu.on_bytes(b"\x1b]0;t\x1b\\a\x1b[1<xb\x1b[2\r;3Hc\x1bPq#0\x1b\\d\x1b[3\x18e");
+    assert_eq!(emu.title, "t");
+    assert_eq!(row_text(&emu, 0), "ab                  ");
+    assert_eq!(row_text(&emu, 1), "  cde               ");
+    for chunk in [&b"\x1b["[..], b"2", b"J"] {
+        emu.on_bytes(chunk);
+    }
+    assert_eq!(row_text(&emu, 1), " ".repeat(20));
+}
+
+#[test]
+fn ascii_runs_wrap_like_single_chars() {
+    let mut wrap = Emu::new(4, 3);
+    wrap.on_bytes(b"abcdefghij");
+    assert_eq!(row_text(&wrap, 0), "abcd");
+    assert_eq!(row_text(&wrap, 2), "ij  ");
+    let mut nowrap = Emu::new(4, 1);
+    nowrap.on_bytes(b"\x1b[?7labcdefg");
+    assert_eq!(row_text(&nowrap, 0), "abcg");
+    let mut insert = Emu::new(4, 1);
+    insert.on_bytes(b"cd\r\x1b[4hab");
+    assert_eq!(row_text(&insert, 0), "abcd");
+}
+
+#[test]
+fn ambiguous_width_follows_the_policy() {
+    let mut emu = Emu::new(6, 1);
+    emu.on_bytes("±x".as_bytes());
+    assert_eq!(emu.cur_x, 2);
+    let mut cjk = Emu::new(6, 1);
+    cjk.width_policy.ambiguous_wide = true;
+    cjk.on_bytes("±x".as_bytes());
+    assert_eq!(cjk.cur_x, 3);
+    assert_eq!(cjk.cell(2, 0).ch, 'x');
+}
+
+#[test]
+fn vs16_widens_narrow_emoji() {
+    let mut emu = Emu::new(8, 1);
+    emu.on_bytes("\u{2764}\u{fe0f}x\u{1f600}\u{fe0f}y\u{fe0e}".as_bytes());
+    assert_eq!(row_text(&emu, 0).trim_end(), "\u{2764}\u{fe0f}x\u{1f600} y");
+    assert_eq!(emu.cur_x, 6);
+
+    let mut plain = Emu::new(8, 1);
+    plain.width_policy = WidthPolicy {
+        emoji_vs16: false,
+        ..WidthPolicy::default()
+    };
+    plain.on_bytes("\u{2764}\u{fe0f}x".as_bytes());
+    assert_eq!(row_text(&plain, 0).trim_end(), "\u{2764}x");
+}
+
+#[test]
+fn unicode_8_keeps_emoji_narrow() {
+    let mut emu = Emu::new(8, 1);
+    emu.width_policy.unicode_version = 8;
+    emu.on_bytes("\u{1f600}x\u{4e16}y".as_bytes());
+    assert_eq!(row_text(&emu, 0).trim_end(), "\u{1f600}x\u{4e16} y");
+    assert_eq!(emu.char_width('\u{1f600}'), 1);
+}
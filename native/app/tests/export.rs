@@ -0,0 +1,109 @@
+use app::term::dump::dump;
+use app::term::emu::Emu;
+use app::term::export::{export, Format, Region, Selection};
+use app::theme::{load_theme, Palette};
+
+fn sample() -> Emu {
+    let mut emu = Emu::new(16, 3);
+    emu.on_bytes(b"old line\r\n");
+    emu.on_bytes(b"\x1b[1;31mERR\x1b[m <b>&\r\n");
+    emu.on_bytes("\x1b[4:3;58;5;2m世界\x1b[m \x1b[38;2;1;2;3;44mrgb\x1b[m\r\n".as_bytes());
+    emu.on_bytes(b"$ ");
+    emu
+}
+
+fn palette() -> Palette {
+    Palette::new(&load_theme("tron").unwrap())
+}
+
+#[test]
+fn plain_text_trims_and_skips_wide_spacers() {
+    let emu = sample();
+    let p = palette();
+    assert_eq!(
+        export(&emu, Region::Screen, Format::Text, &p),
+        "ERR <b>&\n世界 rgb\n$\n"
+    );
+    assert_eq!(
+        export(&emu, Region::History, Format::Text, &p),
+        "old line\nERR <b>&\n世界 rgb\n$\n"
+    );
+    let sel = Selection {
+        start: (2, 1),
+        end: (1, 4),
+    };
+    assert_eq!(
+        export(&emu, Region::Selection(sel), Format::Text, &p),
+        "<b>&\n世\n"
+    );
+}
+
+#[test]
+fn ansi_export_replays_to_the_same_screen() {
+    let emu = sample();
+    let ansi = export(&emu, Region::Screen, Format::Ansi, &palette());
+    let mut replay = Emu::new(16, 3);
+    replay.on_bytes(ansi.trim_end().replace('\n', "\r\n").as_bytes());
+    // Cursor position and scrollback depth are not part of an export.
+    let screen = |d: String| {
+        d.lines()
+            .filter(|l| !l.starts_with("cursor") && !l.starts_with("scrollback"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_eq!(screen(dump(&replay)), screen(dump(&emu)));
+}
+
+#[test]
+fn html_and_svg_use_theme_colors_and_escape_text() {
+    let emu = sample();
+    let p = palette();
+    let bg = format!(
+        "#{:02x}{:02x}{:02x}",
+        p.background.0, p.background.1, p.background.2
+    );
+    let red = p.colors[9];
+    let bright_red = format!("#{:02x}{:02x}{:02x}", red.0, red.1, red.2);
+
+    let html = export(&emu, Region::Screen, Format::Html, &p);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains(&format!("background:{bg}")));
+    assert!(html.contains(&format!(
+        "color:{bright_red};font-weight:bold;\">ERR</span>"
+    )));
+    assert!(html.contains("&lt;b&gt;&amp;"));
+    assert!(html.contains("text-decoration-style:wavy;"));
+    assert!(html.contains("color:#010203;"));
+
+    let svg = export(&emu, Region::Screen, Format::Svg, &p);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.contains(&format!("fill=\"{bg}\"")));
+    assert!(svg.contains(&format!(
+        "fill=\"{bright_red}\" font-weight=\"bold\">ERR</text>"
+    )));
+    assert!(svg.contains("&lt;b&gt;&amp;"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+}
+
+#[test]
+fn format_and_region_parse() {
+    assert_eq!("svg".parse::<Format>().unwrap(), Format::Svg);
+    assert_eq!("history".parse::<Region>().unwrap(), Region::History);
+    assert!("pdf".parse::<Format>().is_err());
+}
+
+#[test]
+fn exports_measure_with_the_width_policy() {
+    let mut emu = Emu::new(8, 1);
+    emu.width_policy.ambiguous_wide = true;
+    emu.on_bytes("\u{b1}x \u{2764}\u{fe0f}!".as_bytes());
+    let p = palette();
+    assert_eq!(
+        export(&emu, Region::Screen, Format::Text, &p),
+        "\u{b1}x \u{2764}\u{fe0f}!\n"
+    );
+    let hits = app::term::search::find(&emu, "x");
+    assert_eq!((hits[0].col, hits[0].width), (2, 1));
+    let hits = app::term::search::find(&emu, "!");
+    assert_eq!(hits[0].col, 6);
+}
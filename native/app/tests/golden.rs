@@ -0,0 +1,130 @@
+//! Golden-file conformance tests for the emulator.
+//!
+//! Every `tests/golden/<name>.<cols>x<rows>.in` is a byte stream. Names
+//! starting with `synthetic-` are written by hand after what the program
+//! emits and may only use sequences the emulator implements; real captures
+//! keep the program's name and are `script -O` logs taken with
+//! `TERM=terminal-ui`, minus the header and trailer lines `script` adds.
+//! Each stream is fed into a fresh `Emu` of that size, whole and split at
+//! many chunk boundaries, and the `term::dump` of the result must match
+//! `<name>.<cols>x<rows>.golden` byte for byte. The chunked runs catch state
+//! that leaks across `on_bytes` calls (UTF-8 and escape sequences cut in half).
+//!
+//! Run with `BLESS=1 cargo test --test golden` to (re)write the golden files
+//! after an intended behaviour change, then review the diff.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use app::term::dump::dump;
+use app::term::emu::Unknown;
+use app::term::Emu;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// `synthetic-vim-insert.40x13.in` -> `(40, 13)`.
+fn size_from_name(path: &Path) -> (usize, usize) {
+    let stem = path.file_stem().unwrap().to_string_lossy();
+    let size = stem
+        .rsplit('.')
+        .next()
+        .and_then(|s| s.split_once('x'))
+        .and_then(|(c, r)| Some((c.parse().ok()?, r.parse().ok()?)));
+    size.unwrap_or_else(|| panic!("{}: expected <name>.<cols>x<rows>.in", path.display()))
+}
+
+fn run(input: &[u8], cols: usize, rows: usize, chunks: impl Iterator<Item = usize>) -> String {
+    let mut emu = Emu::new(cols, rows);
+    let mut rest = input;
+    for n in chunks {
+        if rest.is_empty() {
+            break;
+        }
+        let (head, tail) = rest.split_at(n.clamp(1, rest.len()));
+        emu.on_bytes(head);
+        rest = tail;
+    }
+    emu.on_bytes(rest);
+    dump(&emu)
+}
+
+/// Chunk size sequences to replay each stream with. Besides fixed sizes a
+/// small LCG gives irregular splits that are still reproducible.
+fn chunkings(len: usize) -> Vec<(String, Vec<usize>)> {
+    let mut out: Vec<(String, Vec<usize>)> = [1, 2, 3, 7, 64]
+        .into_iter()
+        .map(|n| (format!("chunks of {n}"), vec![n; len]))
+        .collect();
+    for seed in [1u64, 42, 1337] {
+        let mut state = seed;
+        let sizes = (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 33) as usize % 17 + 1
+            })
+            .collect();
+        out.push((format!("random chunks (seed {seed})"), sizes));
+    }
+    out
+}
+
+#[test]
+fn golden_files() {
+    let bless = std::env::var_os("BLESS").is_some();
+    let mut inputs: Vec<PathBuf> = fs::read_dir(golden_dir())
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "in"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "no golden inputs found");
+
+    let mut failures = Vec::new();
+    for input_path in &inputs {
+        let input = fs::read(input_path).unwrap();
+        let (cols, rows) = size_from_name(input_path);
+        let golden_path = input_path.with_extension("golden");
+        let name = input_path.file_name().unwrap().to_string_lossy();
+
+        let whole = run(&input, cols, rows, std::iter::empty());
+        if name.starts_with("synthetic-") {
+            let mut emu = Emu::new(cols, rows);
+            emu.on_bytes(&input);
+            if emu.unknown != Unknown::default() {
+                failures.push(format!(
+                    "{name}: uses sequences the emulator ignores ({:?})",
+                    emu.unknown
+                ));
+            }
+        }
+        if bless {
+            fs::write(&golden_path, &whole).unwrap();
+        } else {
+            match fs::read_to_string(&golden_path) {
+                Ok(expected) if expected == whole => {}
+                Ok(expected) => failures.push(format!(
+                    "{name}: dump differs from {}\n--- expected\n{expected}--- actual\n{whole}",
+                    golden_path.display()
+                )),
+                Err(_) => failures.push(format!(
+                    "{name}: missing {} (run with BLESS=1)",
+                    golden_path.display()
+                )),
+            }
+        }
+
+        for (label, sizes) in chunkings(input.len()) {
+            let chunked = run(&input, cols, rows, sizes.into_iter());
+            if chunked != whole {
+                failures.push(format!(
+                    "{name}: {label} differs from feeding the stream whole\n--- whole\n{whole}--- chunked\n{chunked}"
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,226 @@
+use std::path::PathBuf;
+
+use app::term::emu::{Color, Emu};
+use app::term::export::{export, export_with, Format, Options, Region};
+use app::term::search::{find, find_between, find_in, lines_between, Match};
+use app::theme::{load_theme, Palette};
+
+fn spill_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("history-test-{name}-{}", std::process::id()))
+}
+
+fn line_text(emu: &Emu, line: usize) -> String {
+    emu.history_line(line)
+        .iter()
+        .map(|c| c.ch)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// `n` numbered lines, the even ones in red.
+fn numbered(emu: &mut Emu, n: usize) {
+    for i in 0..n {
+        let color = if i % 2 == 0 { "31" } else { "0" };
+        emu.on_bytes(format!("\x1b[{color}m{i}\x1b[m\r\n").as_bytes());
+    }
+}
+
+#[test]
+fn spilled_rows_page_back_in() {
+    let dir = spill_dir("page");
+    let mut emu = Emu::new(12, 3);
+    emu.scrollback_limit = 20_000;
+    emu.spill_scrollback_to(dir.clone()).unwrap();
+    numbered(&mut emu, 8000);
+    emu.check_invariants();
+
+    let sb = emu.scrollback.len();
+    assert_eq!(sb, 7998);
+    assert!(emu.scrollback.spilled_rows() > 0);
+    assert!(std::fs::read_dir(&dir).unwrap().count() > 0);
+    for line in [0, 1, 1023, 1024, 3000, sb - 1] {
+        assert_eq!(line_text(&emu, line), line.to_string());
+    }
+    let red = emu.history_line(1000)[0].style;
+    assert_eq!(emu.style(red).fg, Color::Indexed(1));
+
+    emu.scroll_display(sb as isize);
+    assert_eq!(emu.visible_row(0)[0].ch, '0');
+    emu.scroll_display(-1);
+    assert_eq!(emu.visible_row(2)[0].ch, '3');
+
+    let text = export(
+        &emu,
+        Region::History,
+        Format::Text,
+        &Palette::new(&load_theme("tron").unwrap()),
+    );
+    assert!(text.starts_with("0\n1\n2\n"));
+    assert_eq!(text.lines().count(), sb + 3);
+
+    drop(emu);
+    assert!(!dir.exists());
+}
+
+#[test]
+fn evicting_spilled_rows_deletes_pages() {
+    let dir = spill_dir("evict");
+    let mut emu = Emu::new(12, 3);
+    emu.scrollback_limit = 6000;
+    emu.spill_scrollback_to(dir.clone()).unwrap();
+    numbered(&mut emu, 20_000);
+    emu.check_invariants();
+
+    assert_eq!(emu.scrollback.len(), 6000);
+    assert_eq!(line_text(&emu, 0), "13998");
+    let pages = std::fs::read_dir(&dir).unwrap().count();
+    assert!(pages <= 2, "{pages} pages left behind");
+
+    emu.on_bytes(b"\x1b[3J");
+    assert!(emu.scrollback.is_empty());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+    emu.discard_spilled_scrollback();
+    assert!(!dir.exists());
+}
+
+#[test]
+fn styles_of_spilled_rows_survive_compaction() {
+    let dir = spill_dir("styles");
+    let mut emu = Emu::new(12, 3);
+    emu.scrollback_limit = 20_000;
+    emu.spill_scrollback_to(dir).unwrap();
+    emu.on_bytes(b"\x1b[38;2;1;2;3mfirst\x1b[m\r\n");
+    for i in 0..6000 {
+        emu.on_bytes(format!("\x1b[38;2;{};{};0mx\x1b[m\r\n", i % 256, i / 256).as_bytes());
+    }
+    emu.check_invariants();
+    assert!(emu.scrollback.spilled_rows() > 0);
+    let first = emu.history_line(0)[0];
+    assert_eq!(first.ch, 'f');
+    assert_eq!(emu.style(first.style).fg, Color::Rgb(1, 2, 3));
+}
+
+#[test]
+fn search_covers_scrollback_and_screen() {
+    let mut emu = Emu::new(12, 3);
+    emu.on_bytes("needle\r\n世界 needle\r\nno\r\nneedle".as_bytes());
+    assert_eq!(
+        find(&emu, "needle"),
+        vec![
+            Match {
+                line: 0,
+                col: 0,
+                width: 6
+            },
+            Match {
+                line: 1,
+                col: 5,
+                width: 6
+            },
+            Match {
+                line: 3,
+                col: 0,
+                width: 6
+            },
+        ]
+    );
+    assert_eq!(
+        find(&emu, "世界"),
+        vec![Match {
+            line: 1,
+            col: 0,
+            width: 4
+        }]
+    );
+    assert_eq!(find_in(&emu, "needle", 1..3).len(), 1);
+    assert!(find(&emu, "").is_empty());
+}
+
+#[test]
+fn search_reads_spilled_pages() {
+    let dir = spill_dir("search");
+    let mut emu = Emu::new(12, 3);
+    emu.scrollback_limit = 20_000;
+    emu.spill_scrollback_to(dir).unwrap();
+    numbered(&mut emu, 8000);
+    let hits = find(&emu, "1234");
+    assert_eq!(hits.iter().map(|m| m.line).collect::<Vec<_>>(), vec![1234]);
+}
+
+/// Times in these tests are seconds since the epoch, to keep them readable.
+fn at(emu: &mut Emu, secs: u64, bytes: &str) {
+    emu.set_clock(Some(secs * 1000));
+    emu.on_bytes(bytes.as_bytes());
+}
+
+#[test]
+fn rows_are_stamped_when_first_written() {
+    let mut emu = Emu::new(10, 3);
+    at(&mut emu, 1, "one\r\n");
+    at(&mut emu, 2, "two\r\n");
+    assert_eq!(emu.line_times, vec![1000, 2000, 0]);
+    // Rewriting a row keeps its arrival time.
+    at(&mut emu, 3, "\x1b[Htwo\x1b[3H");
+    assert_eq!(emu.line_times[0], 1000);
+    at(&mut emu, 4, "three\r\nfour");
+    assert_eq!(emu.scrollback.time(0), 1000);
+    assert_eq!(emu.line_times, vec![2000, 4000, 4000]);
+    assert_eq!(emu.history_line_time(0), 1000);
+    assert_eq!(emu.history_line_time(3), 4000);
+    emu.scroll_display(1);
+    assert_eq!(emu.visible_line_time(0), 1000);
+    at(&mut emu, 5, "\x1b[2J");
+    assert_eq!(emu.line_times, vec![0, 0, 0]);
+    emu.check_invariants();
+}
+
+#[test]
+fn search_by_time_range() {
+    let mut emu = Emu::new(10, 3);
+    at(&mut emu, 10, "scan a\r\n");
+    at(&mut emu, 20, "scan b\r\n");
+    at(&mut emu, 30, "scan c\r\n");
+    at(&mut emu, 40, "done");
+    assert_eq!(lines_between(&emu, 15_000..35_000), vec![1, 2]);
+    let hits = find_between(&emu, "scan", 15_000..35_000);
+    assert_eq!(hits.iter().map(|m| m.line).collect::<Vec<_>>(), vec![1, 2]);
+    assert!(find_between(&emu, "scan", 0..10_000).is_empty());
+}
+
+#[test]
+fn exports_can_include_times() {
+    let mut emu = Emu::new(10, 2);
+    at(&mut emu, 1, "first\r\n");
+    at(&mut emu, 61, "second");
+    let p = Palette::new(&load_theme("tron").unwrap());
+    let times = Options { times: true };
+    assert_eq!(
+        export_with(&emu, Region::History, Format::Text, &p, times),
+        "1970-01-01T00:00:01.000Z first\n1970-01-01T00:01:01.000Z second\n"
+    );
+    emu.on_bytes(b"\r\n");
+    let ansi = export_with(&emu, Region::Screen, Format::Ansi, &p, times);
+    assert_eq!(
+        ansi,
+        format!(
+            "\x1b[0;2m1970-01-01T00:01:01.000Z \x1b[0msecond\n\x1b[0;2m{} \x1b[0m\n",
+            " ".repeat(24)
+        )
+    );
+}
+
+#[test]
+fn snapshots_keep_row_times() {
+    let mut emu = Emu::new(10, 2);
+    at(&mut emu, 1, "a\r\n");
+    at(&mut emu, 2, "b\r\n");
+    at(&mut emu, 3, "c");
+    let snap = emu.snapshot(1 << 20).unwrap();
+    let mut restored = Emu::new(10, 2);
+    restored.restore(&snap).unwrap();
+    assert_eq!(restored.scrollback.time(0), 1000);
+    assert_eq!(restored.line_times, vec![2000, 3000]);
+    restored.check_invariants();
+}
@@ -0,0 +1,72 @@
+use app::term::emu::{Emu, Unknown};
+use app::term::inspect::inspect;
+
+fn log(emu: &Emu) -> Vec<(String, bool)> {
+    emu.trace().map(|e| (e.text.clone(), e.handled)).collect()
+}
+
+#[test]
+fn unknown_sequences_are_counted_without_a_log() {
+    let mut emu = Emu::new(20, 4);
+    emu.on_bytes(b"\x1b[?1049h\x1b[1m\x1b]1337;x\x07\x1bPq#0\x1b\\\x1bZ\x1b[?25l");
+    assert_eq!(
+        emu.unknown,
+        Unknown {
+            csi: 1,
+            osc: 1,
+            dcs: 1,
+            esc: 1
+        }
+    );
+    assert_eq!(emu.trace().count(), 0);
+}
+
+#[test]
+fn log_marks_unhandled_sequences() {
+    let mut emu = Emu::new(20, 4);
+    emu.set_trace(3);
+    emu.on_bytes(b"\x1b[38:2::1:2:3m\x1b[?1049h\x1b]0;hi\x1b\\\x1b(0\x1bPq\x1b\\");
+    // The oldest entry, the SGR, made room for the last.
+    assert_eq!(
+        log(&emu),
+        [
+            ("OSC 0;hi".to_string(), true),
+            ("ESC (0".to_string(), false),
+            ("DCS q".to_string(), false),
+        ]
+    );
+    emu.set_trace(0);
+    assert!(emu.trace().next().is_none());
+}
+
+#[test]
+fn overlay_shows_state_and_log() {
+    let mut emu = Emu::new(20, 4);
+    emu.set_trace(8);
+    emu.on_bytes(b"\x1b[2;3r\x1b[4h\x1b[31;1m\x1b)0\x0e\x1b[5X\x1b[?9999h");
+    let lines = inspect(&emu);
+    assert_eq!(
+        &lines[..6],
+        [
+            "cursor 0,0 visible block blink",
+            "modes autowrap insert",
+            "margins 1..2 of 20x4",
+            "charset G0=B G1=0 G2=B G3=B GL=G1",
+            "pen fg=1 bold",
+            "unknown csi=1 osc=0 dcs=0 esc=1",
+        ]
+    );
+    assert_eq!(
+        &lines[6..],
+        [
+            "  CSI 2;3r",
+            "  CSI 4h",
+            "  CSI 31;1m",
+            "! ESC )0",
+            "  CSI 5X",
+            "! CSI ?9999h",
+        ]
+    );
+    let entry = emu.trace().last().unwrap();
+    assert!(!entry.handled);
+}
@@ -0,0 +1,121 @@
+use app::term::emu::{Emu, Limits};
+use app::term::event::{Clipboard, EmuEvent};
+use crossbeam_channel::{unbounded, Receiver};
+
+fn with_events(cols: usize, rows: usize) -> (Emu, Receiver<EmuEvent>) {
+    let (tx, rx) = unbounded();
+    let mut emu = Emu::new(cols, rows);
+    emu.set_listener(Box::new(tx));
+    (emu, rx)
+}
+
+fn row_text(emu: &Emu, y: usize) -> String {
+    (0..emu.cols).map(|x| emu.cell(x, y).ch).collect()
+}
+
+#[test]
+fn oversized_strings_are_dropped() {
+    let (mut emu, rx) = with_events(10, 2);
+    emu.limits = Limits {
+        osc_bytes: 16,
+        dcs_bytes: 16,
+        ..Limits::default()
+    };
+    let long = "x".repeat(100);
+    emu.on_bytes(format!("\x1b]2;{long}\x07ok").as_bytes());
+    emu.on_bytes(format!("\x1b]2;{long}").as_bytes());
+    emu.on_bytes(format!("{long}\x1b\\ab").as_bytes());
+    emu.on_bytes(format!("\x1bPq{long}\x1b\\c").as_bytes());
+    assert!(rx.try_iter().next().is_none());
+    assert_eq!(emu.title, "");
+    assert_eq!(row_text(&emu, 0), "okabc     ");
+    assert_eq!((emu.unknown.osc, emu.unknown.dcs), (2, 1));
+
+    emu.on_bytes(b"\x1b]2;short\x07");
+    assert_eq!(emu.title, "short");
+}
+
+#[test]
+fn titles_are_cleaned_cut_and_rate_limited() {
+    let (mut emu, rx) = with_events(10, 2);
+    emu.limits.title_chars = 5;
+    emu.limits.titles_per_sec = 2;
+    emu.set_clock(Some(10_000));
+    emu.on_bytes("\x1b]2;a\u{9b}bcdefg\x07".as_bytes());
+    assert_eq!(emu.title, "abcde");
+    emu.on_bytes(b"\x1b]2;two\x07\x1b]2;three\x07\x1b]2;four\x07");
+    assert_eq!(emu.title, "two");
+    // The newest title of the burst lands once the window closes.
+    emu.tick();
+    assert_eq!(emu.title, "two");
+    emu.set_clock(Some(11_000));
+    emu.tick();
+    assert_eq!(emu.title, "four");
+    emu.tick();
+    emu.on_bytes(b"\x1b]2;five\x07\x1b]2;six\x07");
+    assert_eq!(emu.title, "five");
+    // A title in a new window supersedes the one held back.
+    emu.set_clock(Some(12_000));
+    emu.on_bytes(b"\x1b]2;seven\x07");
+    emu.tick();
+    assert_eq!(emu.title, "seven");
+    let titles: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        titles,
+        vec![
+            EmuEvent::Title("abcde".into()),
+            EmuEvent::Title("two".into()),
+            EmuEvent::Title("four".into()),
+            EmuEvent::Title("five".into()),
+            EmuEvent::Title("seven".into()),
+        ]
+    );
+}
+
+#[test]
+fn dangerous_reports_are_refused_by_default() {
+    let (mut emu, rx) = with_events(10, 2);
+    emu.on_bytes(b"\x1b]2;id\x07\x1b[21t\x1b[20t\x1b]52;c;?\x07");
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [EmuEvent::Title("id".into())]
+    );
+
+    emu.allow_title_reports = true;
+    emu.allow_clipboard_read = true;
+    emu.on_bytes(b"\x1b[21t\x1b[20t\x1b]52;c;?\x07");
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [
+            EmuEvent::PtyWrite(b"\x1b]lid\x1b\\".to_vec()),
+            EmuEvent::PtyWrite(b"\x1b]Lid\x1b\\".to_vec()),
+            EmuEvent::ClipboardLoad {
+                clipboard: Clipboard::Clipboard
+            },
+        ]
+    );
+}
+
+#[test]
+fn wide_rows_lower_the_scrollback_cap() {
+    let mut emu = Emu::new(1000, 2);
+    emu.limits.scrollback_mib = 1;
+    let capacity = emu.scrollback_capacity();
+    assert_eq!(capacity, (1 << 20) / (1000 * 8 + 8));
+    emu.on_bytes("x\r\n".repeat(500).as_bytes());
+    assert_eq!(emu.scrollback.len(), capacity);
+    emu.check_invariants();
+
+    emu.resize(2000, 2);
+    assert_eq!(emu.scrollback.len(), emu.scrollback_capacity());
+    assert!(emu.scrollback.len() < capacity);
+    emu.check_invariants();
+}
+
+#[test]
+fn huge_scrollback_budget_does_not_overflow() {
+    let mut emu = Emu::new(80, 2);
+    // Shifting this into bytes would wrap to zero.
+    emu.limits.scrollback_mib = 1 << (usize::BITS - 20);
+    assert_eq!(emu.scrollback_capacity(), emu.scrollback_limit);
+}
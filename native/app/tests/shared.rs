@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+use app::term::shared::SharedEmu;
+use app::term::Emu;
+use crossbeam_channel::bounded;
+
+#[test]
+fn parser_thread_applies_all_output_in_order() {
+    let emu = SharedEmu::new(Emu::new(10, 3));
+    let (tx, rx) = bounded(4);
+    let parser = emu.spawn_parser(rx);
+    for i in 0..1000 {
+        tx.send(format!("\r\n{i}").into_bytes()).unwrap();
+    }
+    drop(tx);
+    parser.join().unwrap();
+    let emu = emu.lock();
+    let row: String = (0..4).map(|x| emu.cell(x, 2).ch).collect();
+    assert_eq!(row, "999 ");
+    assert_eq!(emu.scrollback.len(), 998);
+}
+
+#[test]
+fn ui_lock_stays_available_under_flood() {
+    let emu = SharedEmu::new(Emu::new(200, 50));
+    let (tx, rx) = bounded(16);
+    let parser = emu.spawn_parser(rx);
+    let flood = std::thread::spawn(move || {
+        let chunk = b"y\r\n".repeat(1 << 16);
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(500) {
+            if tx.send(chunk.clone()).is_err() {
+                break;
+            }
+        }
+    });
+    let mut worst = Duration::ZERO;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(400) {
+        let t = Instant::now();
+        let emu = emu.lock();
+        worst = worst.max(t.elapsed());
+        assert!(emu.cur_y < emu.rows);
+        drop(emu);
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    flood.join().unwrap();
+    parser.join().unwrap();
+    // One 4 KiB chunk is well under a millisecond; leave room for slow CI.
+    assert!(worst < Duration::from_millis(50), "UI waited {worst:?}");
+}
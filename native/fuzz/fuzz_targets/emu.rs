@@ -0,0 +1,39 @@
+//! Feed arbitrary output to the emulator, interleaved with resizes and
+//! viewport scrolling, checking `Emu::check_invariants` after every step.
+#![no_main]
+
+use app::term::Emu;
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Bytes(Vec<u8>),
+    Resize { cols: u8, rows: u8 },
+    ScrollDisplay(i8),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    cols: u8,
+    rows: u8,
+    scrollback: u8,
+    ops: Vec<Op>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut emu = Emu::new(input.cols as usize, input.rows as usize);
+    emu.scrollback_limit = input.scrollback as usize;
+    emu.check_invariants();
+    for op in input.ops {
+        match op {
+            Op::Bytes(bytes) => emu.on_bytes(&bytes),
+            Op::Resize { cols, rows } => emu.resize(cols as usize, rows as usize),
+            Op::ScrollDisplay(delta) => emu.scroll_display(delta as isize),
+        }
+        emu.check_invariants();
+        for y in 0..emu.rows {
+            let _ = emu.visible_row(y);
+        }
+    }
+});
@@ -0,0 +1,9 @@
+//! The ptyd request decoder must reject bad input with an error, never panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ptyd::protocol::decode;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode(data);
+});
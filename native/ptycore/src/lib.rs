@@ -1,12 +1,38 @@
 use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use portable_pty::{CommandBuilder, ExitStatus, NativePtySystem, PtySize, PtySystem};
 use which::which;
 
+pub mod terminfo;
+
+/// What to start in the terminal. By default that is the user's shell;
+/// set `program` to run something else (`ssh`, `python3`, ...) directly.
 pub struct ShellPrefs {
     pub prefer_pwsh: bool,
+    /// Start a login shell (`-l`). Only applies when the program is bash,
+    /// zsh or fish, as found or as given in `program`, and not to a
+    /// `program` given with `args` of its own.
     pub login: bool,
+    /// Ask bash or zsh for an interactive session (`-i`).
+    pub interactive: bool,
+    /// Exported as `TERM_PROGRAM_VERSION`.
+    pub program_version: String,
+    /// Program to run instead of the shell, looked up in `PATH`.
+    pub program: Option<String>,
+    /// Arguments, after any login/interactive flags.
+    pub args: Vec<String>,
+    /// Variables to set, applied after the terminal's own (`TERM`, ...) so
+    /// they can override them.
+    pub env: Vec<(String, String)>,
+    /// Variables to remove from the inherited environment.
+    pub env_remove: Vec<String>,
+    /// Working directory; the home directory if unset.
+    pub cwd: Option<PathBuf>,
+    /// Compile our terminfo entry into `~/.terminfo` if it is missing. When
+    /// off, `TERM` falls back to `xterm-256color` unless it is installed.
+    pub install_terminfo: bool,
 }
 
 impl Default for ShellPrefs {
@@ -14,6 +40,14 @@ impl Default for ShellPrefs {
         Self {
             prefer_pwsh: true,
             login: true,
+            interactive: true,
+            program_version: env!("CARGO_PKG_VERSION").to_string(),
+            program: None,
+            args: Vec::new(),
+            env: Vec::new(),
+            env_remove: Vec::new(),
+            cwd: None,
+            install_terminfo: true,
         }
     }
 }
@@ -29,9 +63,14 @@ pub struct PtyHandle {
     writer: Option<Box<dyn Write + Send>>,
     master: Box<dyn portable_pty::MasterPty + Send>,
     child: Box<dyn portable_pty::Child + Send>,
+    term: &'static str,
 }
 
 impl PtyHandle {
+    /// The `TERM` the child was started with.
+    pub fn term(&self) -> &'static str {
+        self.term
+    }
     pub fn take_reader(&mut self) -> Box<dyn Read + Send> {
         self.reader.take().unwrap()
     }
@@ -79,8 +118,25 @@ pub fn spawn_shell(cols: u16, rows: u16, prefs: ShellPrefs) -> Result<PtyHandle>
         })
         .context("open pty")?;
 
-    let mut cmd = build_shell_command(&prefs)?;
-    cmd.env("TERM", "xterm-256color");
+    let mut cmd = build_command(&prefs)?;
+    let term = if prefs.install_terminfo {
+        terminfo::ensure_terminfo()
+    } else {
+        terminfo::installed_term()
+    };
+    terminfo::apply_term_env(&mut cmd, term, &prefs.program_version);
+    for (key, value) in &prefs.env {
+        cmd.env(key, value);
+    }
+    for key in &prefs.env_remove {
+        cmd.env_remove(key);
+    }
+    if let Some(dir) = &prefs.cwd {
+        if !dir.is_dir() {
+            bail!("working directory {} does not exist", dir.display());
+        }
+        cmd.cwd(dir);
+    }
 
     let child = pair.slave.spawn_command(cmd).context("spawn command")?;
     let reader = Some(pair.master.try_clone_reader().context("clone reader")?);
@@ -90,10 +146,41 @@ pub fn spawn_shell(cols: u16, rows: u16, prefs: ShellPrefs) -> Result<PtyHandle>
         writer,
         master: pair.master,
         child,
+        term,
     })
 }
 
-fn build_shell_command(prefs: &ShellPrefs) -> Result<CommandBuilder> {
+fn build_command(prefs: &ShellPrefs) -> Result<CommandBuilder> {
+    let program = match &prefs.program {
+        Some(program) => program.clone(),
+        None => default_shell(prefs),
+    };
+    let mut cmd = CommandBuilder::new(&program);
+    // `bash -c make` is a command to run as given, not a shell session.
+    let session = prefs.program.is_none() || prefs.args.is_empty();
+    let name = Path::new(&program).file_stem().and_then(|s| s.to_str());
+    if let Some(name) = name.filter(|_| session) {
+        for flag in shell_flags(name, prefs) {
+            cmd.arg(flag);
+        }
+    }
+    cmd.args(&prefs.args);
+    Ok(cmd)
+}
+
+/// Login/interactive flags for shells that understand them.
+fn shell_flags(name: &str, prefs: &ShellPrefs) -> Vec<&'static str> {
+    let mut flags = Vec::new();
+    if prefs.login && matches!(name, "bash" | "zsh" | "fish") {
+        flags.push("-l");
+    }
+    if prefs.interactive && matches!(name, "bash" | "zsh") {
+        flags.push("-i");
+    }
+    flags
+}
+
+fn default_shell(prefs: &ShellPrefs) -> String {
     if cfg!(windows) {
         let mut shells = vec!["cmd.exe"]; // default fallback
         if prefs.prefer_pwsh {
@@ -103,33 +190,13 @@ fn build_shell_command(prefs: &ShellPrefs) -> Result<CommandBuilder> {
             shells.insert(0, "powershell.exe");
             shells.insert(1, "pwsh.exe");
         }
-        let shell = shells
+        shells
             .into_iter()
             .find(|s| which(s).is_ok())
             .map(|s| s.to_string())
-            .unwrap_or_else(|| std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".into()));
-        Ok(CommandBuilder::new(shell))
+            .unwrap_or_else(|| std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".into()))
     } else {
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".into());
-        let mut cmd = CommandBuilder::new(shell.clone());
-        if prefs.login {
-            if let Some(name) = std::path::Path::new(&shell)
-                .file_name()
-                .and_then(|s| s.to_str())
-            {
-                match name {
-                    "bash" | "zsh" => {
-                        cmd.arg("-l");
-                        cmd.arg("-i");
-                    }
-                    "fish" => {
-                        cmd.arg("-l");
-                    }
-                    _ => {}
-                }
-            }
-        }
-        Ok(cmd)
+        std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".into())
     }
 }
 
@@ -0,0 +1,104 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use portable_pty::CommandBuilder;
+
+/// Our own terminfo entry, advertised when it can be installed.
+pub const TERM_NAME: &str = "terminal-ui";
+/// What we advertise when the entry is unavailable (no `tic`, Windows, ...).
+pub const FALLBACK_TERM: &str = "xterm-256color";
+/// Value of `TERM_PROGRAM`.
+pub const TERM_PROGRAM: &str = "terminal-ui";
+
+const TERMINFO_SRC: &str = include_str!("../assets/terminal-ui.terminfo");
+
+/// Make sure the `terminal-ui` terminfo entry is available, compiling it into
+/// `~/.terminfo` with `tic` if needed, and return the `TERM` value to export.
+/// The check runs once per process.
+pub fn ensure_terminfo() -> &'static str {
+    static TERM: OnceLock<&'static str> = OnceLock::new();
+    TERM.get_or_init(|| {
+        if cfg!(windows) {
+            return FALLBACK_TERM;
+        }
+        if is_installed() || install().is_ok() {
+            TERM_NAME
+        } else {
+            FALLBACK_TERM
+        }
+    })
+}
+
+/// Like [`ensure_terminfo`], but never runs `tic`: our entry if it is
+/// already installed, the fallback otherwise.
+pub fn installed_term() -> &'static str {
+    if cfg!(not(windows)) && is_installed() {
+        TERM_NAME
+    } else {
+        FALLBACK_TERM
+    }
+}
+
+/// Export `TERM`, `COLORTERM`, `TERM_PROGRAM` and `TERM_PROGRAM_VERSION` on `cmd`.
+pub fn apply_term_env(cmd: &mut CommandBuilder, term: &str, program_version: &str) {
+    cmd.env("TERM", term);
+    cmd.env("COLORTERM", "truecolor");
+    cmd.env("TERM_PROGRAM", TERM_PROGRAM);
+    cmd.env("TERM_PROGRAM_VERSION", program_version);
+}
+
+fn user_terminfo_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(".terminfo"))
+}
+
+/// Look where ncurses would: `$TERMINFO`, `~/.terminfo`, `$TERMINFO_DIRS`
+/// and the usual system locations. Both the letter (`t/`) and the hex
+/// (`74/`, macOS) directory layouts are checked.
+fn is_installed() -> bool {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Some(dir) = std::env::var_os("TERMINFO") {
+        dirs.push(dir.into());
+    }
+    dirs.extend(user_terminfo_dir());
+    if let Some(list) = std::env::var_os("TERMINFO_DIRS") {
+        dirs.extend(std::env::split_paths(&list).filter(|p| !p.as_os_str().is_empty()));
+    }
+    dirs.extend(
+        ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"]
+            .iter()
+            .map(PathBuf::from),
+    );
+    dirs.iter().any(|dir| {
+        dir.join("t").join(TERM_NAME).is_file() || dir.join("74").join(TERM_NAME).is_file()
+    })
+}
+
+/// Compile the entry with `tic`, fed on stdin so no source file is ever
+/// written to a shared location.
+fn install() -> std::io::Result<()> {
+    let out_dir = user_terminfo_dir()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no HOME"))?;
+    let mut tic = Command::new("tic")
+        .arg("-x")
+        .arg("-o")
+        .arg(&out_dir)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Dropping stdin after the write closes it, so tic sees the end.
+    let written = tic
+        .stdin
+        .take()
+        .map_or(Ok(()), |mut stdin| stdin.write_all(TERMINFO_SRC.as_bytes()));
+    let status = tic.wait()?;
+    written?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("tic exited with {status}")))
+    }
+}
@@ -1,9 +1,17 @@
 use ptycore::{spawn_shell, ShellPrefs};
 use std::io::Read;
 
+/// Default prefs that leave the user's `~/.terminfo` alone.
+fn test_prefs() -> ShellPrefs {
+    ShellPrefs {
+        install_terminfo: false,
+        ..ShellPrefs::default()
+    }
+}
+
 #[test]
 fn spawn_prints_shell() {
-    let mut handle = spawn_shell(80, 24, ShellPrefs::default()).expect("spawn shell");
+    let mut handle = spawn_shell(80, 24, test_prefs()).expect("spawn shell");
     #[cfg(unix)]
     let cmd = b"echo $SHELL\nexit\n".to_vec();
     #[cfg(windows)]
@@ -20,3 +28,80 @@ fn spawn_prints_shell() {
     #[cfg(windows)]
     assert!(output.contains("PSVersion"));
 }
+
+#[cfg(unix)]
+#[test]
+fn exports_terminal_identity() {
+    let mut handle = spawn_shell(80, 24, test_prefs()).expect("spawn shell");
+    handle
+        .write(b"echo \"tp=$TERM_PROGRAM ct=$COLORTERM term=$TERM\"\nexit\n")
+        .expect("write");
+    handle.close();
+    let mut reader = handle.take_reader();
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).expect("read");
+    handle.wait().ok();
+    let output = String::from_utf8_lossy(&out);
+    let term = ptycore::terminfo::installed_term();
+    assert!(output.contains(&format!("tp=terminal-ui ct=truecolor term={term}")));
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_program_with_args_env_and_cwd() {
+    let dir = std::env::temp_dir().canonicalize().expect("temp dir");
+    let prefs = ShellPrefs {
+        program: Some("sh".into()),
+        args: vec![
+            "-c".into(),
+            "echo \"greeting=$GREETING dir=$(pwd -P) tp=${TERM_PROGRAM-unset}\"".into(),
+        ],
+        env: vec![("GREETING".into(), "hi".into())],
+        env_remove: vec!["TERM_PROGRAM".into()],
+        cwd: Some(dir.clone()),
+        ..test_prefs()
+    };
+    let mut handle = spawn_shell(80, 24, prefs).expect("spawn program");
+    let mut reader = handle.take_reader();
+    let mut out = Vec::new();
+    // The read ends with EIO once the program exits and the pty closes.
+    let _ = reader.read_to_end(&mut out);
+    handle.wait().ok();
+    let output = String::from_utf8_lossy(&out);
+    assert!(
+        output.contains(&format!("greeting=hi dir={} tp=unset", dir.display())),
+        "{output}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn program_with_args_gets_no_shell_flags() {
+    let prefs = ShellPrefs {
+        program: Some("bash".into()),
+        args: vec![
+            "-c".into(),
+            "shopt -q login_shell && echo login=yes || echo login=no; echo flags=$-".into(),
+        ],
+        ..test_prefs()
+    };
+    let mut handle = spawn_shell(80, 24, prefs).expect("spawn program");
+    let mut reader = handle.take_reader();
+    let mut out = Vec::new();
+    let _ = reader.read_to_end(&mut out);
+    handle.wait().ok();
+    let output = String::from_utf8_lossy(&out);
+    assert!(output.contains("login=no"), "{output}");
+    let flags = output.split("flags=").nth(1).unwrap_or_default();
+    assert!(!flags.trim_end().contains('i'), "{output}");
+}
+
+#[test]
+fn missing_cwd_is_an_error() {
+    let prefs = ShellPrefs {
+        cwd: Some(std::env::temp_dir().join("ptycore-no-such-dir")),
+        ..test_prefs()
+    };
+    let err = spawn_shell(80, 24, prefs).err().expect("spawn fails");
+    assert!(err.to_string().contains("does not exist"), "{err}");
+}
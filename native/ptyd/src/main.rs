@@ -5,35 +5,11 @@ use std::sync::{Arc, Mutex};
 use anyhow::{anyhow, Context, Result};
 use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
 use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
-use serde::{Deserialize, Serialize};
+use ptyd::protocol::{decode, FromPty, ToPty};
 
 #[cfg(windows)]
 use which::which;
 
-#[derive(Deserialize)]
-#[serde(tag = "t")]
-enum ToPty {
-    #[serde(rename = "i")]
-    Input { data: String },
-    #[serde(rename = "r")]
-    Resize { cols: u16, rows: u16 },
-    #[serde(rename = "s")]
-    Signal { sig: String },
-}
-
-#[derive(Serialize)]
-#[serde(tag = "t")]
-enum FromPty {
-    #[serde(rename = "o")]
-    Output { data: String, seq: u64 },
-    #[serde(rename = "x")]
-    Exit {
-        code: i32,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        signal: Option<String>,
-    },
-}
-
 fn main() -> Result<()> {
     let pty_system = NativePtySystem::default();
     let mut size = PtySize {
@@ -47,7 +23,9 @@ fn main() -> Result<()> {
     let mut cmd = build_shell_command()?;
     #[cfg(unix)]
     {
-        cmd.env("TERM", "xterm-256color");
+        let version = format!("{}+{}", env!("CARGO_PKG_VERSION"), env!("GIT_SHA"));
+        let term = ptycore::terminfo::ensure_terminfo();
+        ptycore::terminfo::apply_term_env(&mut cmd, term, &version);
     }
 
     let mut child = pair.slave.spawn_command(cmd)?;
@@ -79,21 +57,35 @@ fn main() -> Result<()> {
         Ok(())
     });
 
-    for line in io::stdin().lock().lines() {
-        let line = line?;
-        if line.trim().is_empty() {
-            continue;
+    let mut stdin = io::stdin().lock();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if stdin.read_until(b'\n', &mut line)? == 0 {
+            break;
         }
-        let msg: ToPty = serde_json::from_str(&line)?;
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        // A bad message from the client must not take the shell down with it.
+        let msg = match decode(&line) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("ptyd: ignoring malformed message: {e:#}");
+                continue;
+            }
+        };
         match msg {
             ToPty::Input { data } => {
-                let bytes = B64.decode(data)?;
-                writer.write_all(&bytes)?;
+                writer.write_all(&data)?;
             }
             ToPty::Resize { cols, rows } => {
                 size.cols = cols;
                 size.rows = rows;
-                master.resize(size)?;
+                if let Err(e) = master.resize(size) {
+                    eprintln!("ptyd: resize to {cols}x{rows} failed: {e:#}");
+                }
             }
             ToPty::Signal { sig } => {
                 forward_signal(&mut *child, &sig)?;
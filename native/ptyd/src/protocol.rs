@@ -0,0 +1,57 @@
+//! Line-delimited JSON spoken between the editor extension and `ptyd`.
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A request read from stdin, one JSON object per line.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "t")]
+pub enum ToPty {
+    #[serde(rename = "i")]
+    Input {
+        #[serde(deserialize_with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+    #[serde(rename = "r")]
+    Resize { cols: u16, rows: u16 },
+    #[serde(rename = "s")]
+    Signal { sig: String },
+}
+
+/// An event written to stdout, one JSON object per line.
+#[derive(Serialize)]
+#[serde(tag = "t")]
+pub enum FromPty {
+    #[serde(rename = "o")]
+    Output { data: String, seq: u64 },
+    #[serde(rename = "x")]
+    Exit {
+        code: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signal: Option<String>,
+    },
+}
+
+/// Decode one input line, without its newline. Blank lines yield `None`;
+/// anything that is not a well-formed request (not UTF-8, bad JSON, bad
+/// base64, a zero-sized resize) is an error the caller is expected to
+/// report and skip.
+pub fn decode(line: &[u8]) -> Result<Option<ToPty>> {
+    let line = std::str::from_utf8(line).context("not UTF-8")?;
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    let msg: ToPty = serde_json::from_str(line)?;
+    if let ToPty::Resize { cols, rows } = msg {
+        if cols == 0 || rows == 0 {
+            bail!("invalid size {cols}x{rows}");
+        }
+    }
+    Ok(Some(msg))
+}
+
+fn base64_bytes<'de, D: Deserializer<'de>>(de: D) -> Result<Vec<u8>, D::Error> {
+    let s = String::deserialize(de)?;
+    B64.decode(s).map_err(serde::de::Error::custom)
+}
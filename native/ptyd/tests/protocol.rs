@@ -0,0 +1,40 @@
+use ptyd::protocol::{decode, ToPty};
+
+#[test]
+fn decodes_requests() {
+    assert_eq!(
+        decode(br#"{"t":"i","data":"bHMK"}"#).unwrap(),
+        Some(ToPty::Input {
+            data: b"ls\n".to_vec()
+        })
+    );
+    assert_eq!(
+        decode(br#"{"t":"r","cols":100,"rows":30}"#).unwrap(),
+        Some(ToPty::Resize {
+            cols: 100,
+            rows: 30
+        })
+    );
+    assert_eq!(decode(b"  ").unwrap(), None);
+}
+
+#[test]
+fn rejects_malformed_requests() {
+    for line in [
+        "{",
+        r#"{"t":"i"}"#,
+        r#"{"t":"i","data":"not base64!"}"#,
+        r#"{"t":"r","cols":0,"rows":30}"#,
+        r#"{"t":"r","cols":70000,"rows":30}"#,
+        r#"{"t":"?"}"#,
+        "\u{0}",
+    ] {
+        assert!(
+            decode(line.as_bytes()).is_err(),
+            "{line:?} should be rejected"
+        );
+    }
+    for line in [&b"\xff"[..], br#"{"t":"s","sig":"\xc3("}"#] {
+        assert!(decode(line).is_err(), "{line:?} should be rejected");
+    }
+}